@@ -11,7 +11,7 @@ target = "~/.config/gtk-3.0/settings.ini"
 content = """
 [Settings]
 gtk-application-prefer-dark-theme=1
-gtk-font-name={{ fonts.ui }} {{ fonts.size_ui }}
+gtk-font-name={{ font.ui | gtk_font }}
 gtk-icon-theme-name=Nordzy-purple-dark
 gtk-cursor-theme-name=oreo_sweet_dracula_violet_small
 gtk-cursor-theme-size=24
@@ -34,7 +34,7 @@ target = "~/.config/gtk-4.0/settings.ini"
 content = """
 [Settings]
 gtk-application-prefer-dark-theme=1
-gtk-font-name={{ fonts.ui }} {{ fonts.size_ui }}
+gtk-font-name={{ font.ui | gtk_font }}
 gtk-icon-theme-name=Colloid-Purple-Dracula
 gtk-cursor-theme-name=oreo_sweet_dracula_violet_small
 gtk-cursor-theme-size=24
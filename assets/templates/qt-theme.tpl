@@ -28,7 +28,7 @@ style=Fusion
 
 [Fonts]
 fixed="Monospace,10,-1,5,50,0,0,0,0,0"
-general="{{ fonts.ui }},{{ fonts.size_ui }},-1,5,50,0,0,0,0,0"
+general="{{ font.ui | qt_font }}"
 """
 
 # Qt6ct Color Scheme
@@ -54,7 +54,7 @@ style=Fusion
 
 [Fonts]
 fixed="Monospace,10,-1,5,50,0,0,0,0,0"
-general="{{ fonts.ui }},{{ fonts.size_ui }},-1,5,50,0,0,0,0,0"
+general="{{ font.ui | qt_font(version=6) }}"
 """
 
 # KDE Globals
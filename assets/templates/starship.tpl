@@ -4,6 +4,7 @@ version = "0.1.0"
 authors = ["Kitchn"]
 description = "Starship prompt theme with trapezoid segments"
 license = "MIT"
+after = ["starship-sessions"]
 
 [[targets]]
 target = "~/.config/starship/starship.toml"
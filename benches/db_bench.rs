@@ -13,6 +13,9 @@ fn create_dummy_template(id: usize) -> Template {
             repository: None,
             license: None,
             ignored: false,
+            depends_on: vec![],
+            after: vec![],
+            overrides: vec![],
         },
         targets: vec![],
         files: vec![],
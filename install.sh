@@ -221,6 +221,7 @@ size_ui = "11"
 
 [icons]
 [icons.nerdfont]
+fallback = "ascii"
 success = ""
 error = ""
 warn = ""
@@ -1,14 +1,21 @@
-use anyhow::{Context, Result, anyhow};
+use anyhow::Result;
 use clap::{CommandFactory, Parser};
-use hyprink::cli::args::Cli;
+use hyprink::cli::args::{Cli, Commands};
 use hyprink::cli::commands;
+use hyprink::cli::exit::{self, Failure};
+use hyprink::cli::lock::{self, LockMode, Wait};
 use hyprink::cli::logging::{init_logging, spawn_debug_viewer};
-use std::env;
-use std::fs;
-use std::os::unix::io::AsRawFd;
+use hyprink::db::Store;
 use tracing::{debug, warn};
 
-fn main() -> Result<()> {
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("Error: {:?}", e);
+        std::process::exit(exit::code_of(&e));
+    }
+}
+
+fn run() -> Result<()> {
     let cli = Cli::parse();
 
     // If --debug, spawn hyprdt viewer if needed
@@ -19,13 +26,29 @@ fn main() -> Result<()> {
     // Init Logging
     let logging_enabled = init_logging(cli.debug)?;
 
-    // Acquire global lock (clients only)
-    let _lock_file = match acquire_lock() {
-        Ok(f) => Some(f),
+    // A running daemon applies on our behalf, without racing for the lock
+    if let Some(cmd) = &cli.command
+        && commands::daemon::forward(cmd, &cli.global)?
+    {
+        return Ok(());
+    }
+
+    // Acquire global lock (clients only); watch and the daemon take it per apply instead
+    let wait = Wait::from_arg(cli.global.wait);
+    let mode = cli
+        .command
+        .as_ref()
+        .and_then(Commands::lock_mode)
+        .map(|mode| match mode {
+            // Loading migrates or recovers the store, which writes it
+            LockMode::Shared if Store::needs_repair(&commands::db_path()) => LockMode::Exclusive,
+            mode => mode,
+        });
+    let _lock_file = match mode.map(|mode| lock::acquire(mode, wait)).transpose() {
+        Ok(f) => f,
         Err(e) => {
             warn!("Failed to acquire global lock: {}", e);
-            eprintln!("Error: {}", e);
-            return Ok(());
+            return Err(Failure::new(exit::LOCK_UNAVAILABLE, e.to_string()).into());
         }
     };
 
@@ -41,44 +64,9 @@ fn main() -> Result<()> {
             if logging_enabled {
                 debug!("Executing command: {:?}", cmd);
             }
-            commands::process_command(cmd)?;
+            commands::process_command(cmd, &cli.global)?;
         }
     }
 
     Ok(())
 }
-
-fn acquire_lock() -> Result<fs::File> {
-    let runtime_dir = directories::BaseDirs::new()
-        .and_then(|d| d.runtime_dir().map(|p| p.to_path_buf()))
-        .unwrap_or_else(env::temp_dir);
-
-    debug!("Using runtime directory for lock: {:?}", runtime_dir);
-
-    if !runtime_dir.exists() {
-        let _ = fs::create_dir_all(&runtime_dir);
-    }
-
-    let lock_path = runtime_dir.join("hyprink.lock");
-    let file = fs::OpenOptions::new()
-        .create(true)
-        .write(true)
-        .truncate(true)
-        .open(&lock_path)
-        .with_context(|| format!("Failed to open lock file at {:?}", lock_path))?;
-
-    let fd = file.as_raw_fd();
-    let ret = unsafe { libc::flock(fd, libc::LOCK_EX | libc::LOCK_NB) };
-
-    if ret != 0 {
-        let err = std::io::Error::last_os_error();
-        return Err(anyhow!(
-            "Could not acquire lock for {:?} (another instance running?). OS Error: {} (code: {:?})",
-            lock_path,
-            err,
-            err.raw_os_error()
-        ));
-    }
-
-    Ok(file)
-}
@@ -1,4 +1,7 @@
-use clap::{Parser, Subcommand};
+use super::lock::LockMode;
+use super::output::OutputFormat;
+use crate::packager::Compression;
+use clap::{Args, Parser, Subcommand};
 use std::path::PathBuf;
 
 #[derive(Parser)]
@@ -14,26 +17,115 @@ pub struct Cli {
     /// Enable debug mode with verbose logging in a separate terminal
     #[arg(long, global = true)]
     pub debug: bool,
+
+    #[command(flatten)]
+    pub global: GlobalArgs,
+}
+
+/// Options shared by every subcommand.
+#[derive(Args, Debug, Clone, Default)]
+pub struct GlobalArgs {
+    /// Write all template output beneath DIR instead of the real paths (env: HYPRINK_ROOT)
+    #[arg(long, global = true, value_name = "DIR")]
+    pub root: Option<PathBuf>,
+
+    /// Output format for list, show, render, status, apply, lint, config check and pkg
+    #[arg(long, global = true, value_enum, default_value_t)]
+    pub format: OutputFormat,
+
+    /// Shorthand for `--format json`
+    #[arg(long, global = true)]
+    pub json: bool,
+
+    /// Wait for another running instance instead of failing (forever, or up to SECONDS)
+    #[arg(
+        long,
+        global = true,
+        value_name = "SECONDS",
+        num_args = 0..=1,
+        require_equals = true
+    )]
+    pub wait: Option<Option<u64>>,
+}
+
+impl GlobalArgs {
+    pub fn output_format(&self) -> OutputFormat {
+        if self.json {
+            OutputFormat::Json
+        } else {
+            self.format
+        }
+    }
 }
 
 #[derive(Subcommand, Debug)]
 pub enum Commands {
     /// Add .tpl templates or .pkg packages to the store
-    Add { path: PathBuf },
-    /// Pack .tpl templates from a directory into a .pkg package
+    Add {
+        path: PathBuf,
+        /// Approve every reload hook in PATH without asking
+        #[arg(long)]
+        trust: bool,
+    },
+    /// Manage the package repositories used by install, search and upgrade
+    Repo {
+        #[command(subcommand)]
+        command: RepoCommands,
+    },
+    /// Find packages in the repositories by name or description
+    Search { term: String },
+    /// Install a package from the repositories by name
+    Install {
+        /// NAME or NAME@VERSION (a semver requirement: `1.2` means `^1.2`, `=1.2.0` is exact)
+        spec: String,
+        /// Approve every reload hook in the package without asking
+        #[arg(long)]
+        trust: bool,
+    },
+    /// Install newer versions of packages from the repositories (all if omitted)
+    Upgrade {
+        /// Package name as shown by `list`
+        name: Option<String>,
+        /// Approve every reload hook in the upgraded packages without asking
+        #[arg(long)]
+        trust: bool,
+    },
+    /// Choose between themes installed from packages
+    Theme {
+        #[command(subcommand)]
+        command: ThemeCommands,
+    },
+    /// Look into a .pkg package without installing it
+    Pkg {
+        #[command(subcommand)]
+        command: PkgCommands,
+    },
+    /// Pack a directory of templates and assets into a .pkg package
     Pack {
-        /// Directory containing .tpl files
+        /// Directory to pack, subdirectories included (honours .hyprinkignore)
         input: PathBuf,
         /// Output .pkg file (optional, defaults to <dirname>.pkg)
         #[arg(short, long)]
         output: Option<PathBuf>,
+        /// Sign the package with a key from `hyprink keygen` (name or .key file)
+        #[arg(long, value_name = "KEY")]
+        sign: Option<String>,
+        /// How archive entries are compressed
+        #[arg(long, value_enum, default_value_t = Compression::Deflate)]
+        compression: Compression,
+        /// Only pack files matching this glob (repeatable)
+        #[arg(long, value_name = "GLOB")]
+        include: Vec<String>,
+        /// Leave out files matching this .hyprinkignore pattern (repeatable)
+        #[arg(long, value_name = "PATTERN")]
+        exclude: Vec<String>,
     },
     /// Apply all templates from store to the system
     Apply {
         /// Persistently toggle force mode (always overwrite)
         #[arg(long)]
         toggle_force: bool,
-        /// Force overwrite for this run only
+        /// Force overwrite for this run only, including files edited since the last apply
         #[arg(long)]
         force: bool,
     },
@@ -44,6 +136,184 @@ pub enum Commands {
     },
     /// Compile config into binary cache for faster startup
     Compile,
+    /// Recompile and re-apply whenever the config or a synced directory changes (Linux only)
+    Watch {
+        /// Milliseconds without further changes before acting on a burst of events
+        #[arg(long, value_name = "MS", default_value_t = 200)]
+        debounce: u64,
+    },
+    /// Keep the store and config in memory and serve requests on a Unix socket
+    Daemon {
+        #[command(subcommand)]
+        command: Option<DaemonCommands>,
+    },
+    /// Mirror a directory of .tpl/.pkg files into the store (all synced directories if omitted)
+    Sync {
+        /// Directory to sync and remember
+        dir: Option<PathBuf>,
+        /// Stop syncing the given directory (templates stay in the store)
+        #[arg(long, requires = "dir")]
+        forget: bool,
+        /// Apply templates afterwards if anything changed
+        #[arg(long)]
+        apply: bool,
+    },
+    /// Inspect and repair the template store
+    Store {
+        #[command(subcommand)]
+        command: StoreCommands,
+    },
+    /// Re-read changed template sources and update the store
+    Update {
+        /// Only update this template
+        name: Option<String>,
+        /// Accept sources whose version is lower than the stored one, or not comparable to it
+        #[arg(long)]
+        allow_downgrade: bool,
+    },
+    /// Show the manifest, targets, hooks, requirements and source of a stored template
+    Show { name: String },
+    /// Print the rendered output of a stored template to stdout
+    Render {
+        name: String,
+        /// Only render this output: its index as listed by `show`, or its target path
+        #[arg(short, long, value_name = "N")]
+        target: Option<String>,
+        /// Lay the [theme] table of FILE over the configured theme
+        #[arg(long, value_name = "FILE")]
+        theme: Option<PathBuf>,
+    },
+    /// Compare each target on disk with what hyprink last wrote there
+    Status {
+        /// Only check this template
+        name: Option<String>,
+        /// Print a diff for modified and stale targets
+        #[arg(long)]
+        diff: bool,
+    },
+    /// Render templates in memory and report errors without writing anything
+    Lint {
+        /// Stored template name, or a .tpl/.pkg file (default: every stored template)
+        template: Option<String>,
+    },
+    /// Inspect the hyprink config
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommands,
+    },
+    /// Review and approve the reload hooks of stored templates
+    Trust {
+        /// Stored template names
+        #[arg(required = true)]
+        names: Vec<String>,
+        /// Approve without asking
+        #[arg(long)]
+        yes: bool,
+    },
+    /// Generate an Ed25519 key pair for signing packages and trust it locally
+    Keygen {
+        /// Key name (defaults to $USER)
+        name: Option<String>,
+        /// Replace an existing key of the same name
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum DaemonCommands {
+    /// Re-apply with the [theme] table of FILE laid over the configured theme (reset if omitted)
+    Theme { file: Option<PathBuf> },
+    /// Print daemon events as JSON lines until interrupted
+    Events,
+    /// Stop the running daemon
+    Stop,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum RepoCommands {
+    /// Remember a repository: a directory or file:// URL containing index.toml
+    Add { location: String },
+    /// Forget a repository
+    Remove { location: String },
+    /// Show the remembered repositories
+    List,
+    /// Write index.toml for every .pkg below DIR, making it a repository
+    Index { dir: PathBuf },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ThemeCommands {
+    /// Show installed themes; the selected one is marked with *
+    List,
+    /// Lay theme NAME over hyprink.conf from now on
+    Use {
+        name: String,
+        /// Apply templates afterwards
+        #[arg(long)]
+        apply: bool,
+    },
+    /// Go back to hyprink.conf without a theme
+    Reset {
+        /// Apply templates afterwards
+        #[arg(long)]
+        apply: bool,
+    },
+    /// Delete an installed theme
+    Remove { name: String },
+}
+
+impl ThemeCommands {
+    /// Whether templates should be re-applied if the selection changed.
+    pub fn apply(&self) -> bool {
+        matches!(
+            self,
+            Self::Use { apply: true, .. } | Self::Reset { apply: true }
+        )
+    }
+}
+
+#[derive(Subcommand, Debug)]
+pub enum PkgCommands {
+    /// Show metadata, templates, target paths and hook commands of a package
+    Inspect { package: PathBuf },
+    /// Check signature and checksums, and lint every template against the current config
+    Verify { package: PathBuf },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ConfigCommands {
+    /// Parse the config without the cache and report suspicious values
+    Check,
+}
+
+impl Commands {
+    /// Lock `main` takes before running the command; `None` for long-running
+    /// commands that lock around each change instead.
+    pub fn lock_mode(&self) -> Option<LockMode> {
+        match self {
+            Commands::Watch { .. } | Commands::Daemon { .. } => None,
+            Commands::List { command: None }
+            | Commands::Show { .. }
+            | Commands::Status { .. }
+            | Commands::Render { .. }
+            | Commands::Lint { .. }
+            | Commands::Config { .. }
+            | Commands::Pack { .. }
+            | Commands::Pkg { .. }
+            | Commands::Search { .. }
+            | Commands::Theme {
+                command: ThemeCommands::List,
+            }
+            | Commands::Repo {
+                command: RepoCommands::List | RepoCommands::Index { .. },
+            }
+            | Commands::Store {
+                command: StoreCommands::Verify | StoreCommands::Export { .. },
+            } => Some(LockMode::Shared),
+            _ => Some(LockMode::Exclusive),
+        }
+    }
 }
 
 #[derive(Subcommand, Debug)]
@@ -55,3 +325,30 @@ pub enum ListCommands {
     /// Disable a template (set ignored status)
     Disable { name: String },
 }
+
+#[derive(Subcommand, Debug)]
+pub enum StoreCommands {
+    /// Check the store database and its snapshots for corruption
+    Verify,
+    /// Replace the store with a snapshot (the newest valid one if omitted)
+    Restore {
+        /// Snapshot file name as shown by `store verify`
+        snapshot: Option<String>,
+    },
+    /// Export templates to a directory of .tpl files or a single .pkg
+    Export {
+        /// Destination directory, or a path ending in .pkg
+        dest: PathBuf,
+        /// Only export these templates (default: all)
+        #[arg(short, long = "template", value_name = "NAME")]
+        templates: Vec<String>,
+    },
+    /// Merge templates from a `store export` directory or .pkg
+    Import {
+        /// Exported directory or .pkg
+        source: PathBuf,
+        /// Replace stored templates that differ from the imported ones
+        #[arg(long)]
+        overwrite: bool,
+    },
+}
@@ -1,3 +1,4 @@
+use super::logging::{info, warn};
 use crate::config;
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
@@ -36,6 +37,25 @@ impl CliConfig {
         Ok(())
     }
 
+    /// Whether an apply should force, flipping the persistent mode first if `toggle` is set.
+    pub fn resolve_force(toggle: bool, force: bool) -> bool {
+        let mut cli_conf = Self::load().unwrap_or_default();
+        if toggle {
+            cli_conf.force_apply = !cli_conf.force_apply;
+
+            if cli_conf.force_apply {
+                info("APPLY", "FORCE MODE ENABLED (persistent)");
+            } else {
+                info("APPLY", "FORCE MODE DISABLED (persistent)");
+            }
+
+            if let Err(e) = cli_conf.save() {
+                warn("APPLY", &format!("Failed to save CLI config: {}", e));
+            }
+        }
+        force || cli_conf.force_apply
+    }
+
     fn get_config_path() -> Result<PathBuf> {
         let data_dir = config::data_dir();
         Ok(data_dir.join("cli.toml"))
@@ -1,54 +1,303 @@
-use super::super::logging::info;
-use crate::config::Config;
-use crate::db::Store;
+use super::super::logging::{debug, error, info, warn};
+use crate::config::{self, Config, SignaturePolicy};
+use crate::db::{PackageRef, Provenance, Store};
+use crate::digest;
+use crate::packager;
+use crate::processor;
+use crate::resolver;
+use crate::signing::{self, Verification};
 use crate::template::Template;
-use anyhow::{Context, Result, anyhow};
+use crate::themes;
+use anyhow::{Context, Result, anyhow, bail};
 use std::fs;
-use std::io::Read;
+use std::io::{self, IsTerminal, Write};
 use std::path::Path;
 
-pub fn add_template(path: &Path, db: &mut Store, _config: &Config) -> Result<Vec<Template>> {
+pub fn add_template(
+    path: &Path,
+    db: &mut Store,
+    config: &Config,
+    trust: bool,
+) -> Result<Vec<Template>> {
     let mut installed_list = Vec::new();
 
     if !path.exists() {
         return Err(anyhow!("File not found: {:?}", path));
     }
 
-    if path.extension().is_some_and(|ext| ext == "pkg") {
-        let file = fs::File::open(path)?;
-        let mut archive = zip::ZipArchive::new(file)?;
-
-        for i in 0..archive.len() {
-            let mut file = archive.by_index(i)?;
-            if file.name().ends_with(".tpl") {
-                let mut content = String::new();
-                file.read_to_string(&mut content)?;
-                let tpl: Template = toml::from_str(&content).with_context(|| {
-                    format!("Failed to parse template inside package: {}", file.name())
-                })?;
-
-                info(
-                    "ADD",
-                    &format!("added {} v{}", tpl.manifest.name, tpl.manifest.version),
-                );
-
-                let tpl_clone = tpl.clone();
-                db.add(tpl)?;
-                installed_list.push(tpl_clone);
+    let source = fs::canonicalize(path)?;
+    let checksum = digest::sha256_file(&source)?;
+    let added_at = chrono::Utc::now().timestamp();
+
+    let is_package = source.extension().is_some_and(|ext| ext == "pkg");
+    if is_package {
+        check_signature(&source, config.packages.signatures)?;
+    }
+    let parsed = read_source(&source)?;
+    if is_package {
+        match parsed.first().and_then(|s| s.package.as_ref()) {
+            Some(pkg) => info("ADD", &format!("package {} v{}", pkg.name, pkg.version)),
+            None => warn(
+                "ADD",
+                &format!(
+                    "{} has no {}; its contents cannot be verified",
+                    source.display(),
+                    packager::MANIFEST
+                ),
+            ),
+        }
+    }
+    // Every hook is reviewed before anything is installed
+    let mut approvals = Vec::new();
+    for parsed in &parsed {
+        if let Some(hook) = review_hook(&parsed.template, db, trust)? {
+            approvals.push((parsed.template.manifest.name.clone(), hook));
+        }
+    }
+
+    for parsed in parsed {
+        let provenance = parsed.provenance(&source, &checksum, added_at);
+        install(parsed.template, provenance, db, &mut installed_list)?;
+    }
+    for (name, hook) in approvals {
+        db.approve_hook(&name, hook);
+    }
+
+    check_requires(&installed_list, db, "ADD")?;
+    // Themes are written straight to disk, so only once nothing else can fail
+    if is_package {
+        register_themes(&source)?;
+    }
+    Ok(installed_list)
+}
+
+/// Install the themes a package ships so `hyprink theme use` can select them.
+/// `hyprink.conf` is left alone.
+fn register_themes(package: &Path) -> Result<()> {
+    let dir = config::themes_dir();
+    for (name, content) in packager::read_package(package)?.themes {
+        let replaced = themes::install(&dir, &name, &content)?;
+        let verb = if replaced { "updated" } else { "added" };
+        info(
+            "ADD",
+            &format!(
+                "{} theme {} (select it with `hyprink theme use {}`)",
+                verb, name, name
+            ),
+        );
+    }
+    Ok(())
+}
+
+/// Show the reload hook of `tpl` and have it approved, unless this exact
+/// command was approved for the template before. Returns the digest to record.
+fn review_hook(tpl: &Template, db: &Store, trust: bool) -> Result<Option<String>> {
+    let Some(cmd) = &tpl.hooks.reload else {
+        return Ok(None);
+    };
+    let name = &tpl.manifest.name;
+    let hook = processor::hook_digest(cmd);
+    if db.approved_hooks().get(name) == Some(&hook) {
+        debug("ADD", &format!("hook of {} was approved before", name));
+        return Ok(Some(hook));
+    }
+
+    warn(
+        "ADD",
+        &format!("{} runs this command after every apply: {}", name, cmd),
+    );
+    if trust {
+        info("ADD", &format!("approved hook of {} (--trust)", name));
+        return Ok(Some(hook));
+    }
+    if !io::stdin().is_terminal() {
+        bail!(
+            "Refusing to add {}: its reload hook needs approval (re-run with --trust)",
+            name
+        );
+    }
+
+    if ask(&format!("Allow {} to run this hook?", name))? {
+        Ok(Some(hook))
+    } else {
+        bail!("Refusing to add {}: reload hook not approved", name)
+    }
+}
+
+/// Ask a yes/no question on the terminal; anything but yes is no.
+pub fn ask(question: &str) -> Result<bool> {
+    eprint!("{} [y/N] ", question);
+    io::stderr().flush()?;
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+/// Check the detached signature of a package against the trusted keys, as
+/// far as `policy` asks. A signature that does not match always fails.
+pub fn check_signature(package: &Path, policy: SignaturePolicy) -> Result<()> {
+    if policy == SignaturePolicy::Off {
+        return Ok(());
+    }
+    let trusted = signing::load_trusted(&config::trusted_keys_dir())?;
+    let name = package.display();
+    match signing::verify_file(package, &trusted)? {
+        Verification::Trusted { key, fingerprint } => {
+            info(
+                "ADD",
+                &format!("{} is signed by {} ({})", name, key, fingerprint),
+            );
+            return Ok(());
+        }
+        Verification::Invalid { reason } => bail!("Refusing {}: {}", name, reason),
+        Verification::Unsigned if policy == SignaturePolicy::Require => {
+            bail!("Refusing {}: package is not signed", name)
+        }
+        Verification::UntrustedKey { key, fingerprint } if policy == SignaturePolicy::Require => {
+            bail!(
+                "Refusing {}: signed by {} ({}), which is not in {}",
+                name,
+                key,
+                fingerprint,
+                config::trusted_keys_dir().display()
+            )
+        }
+        Verification::Unsigned => warn("ADD", &format!("{} is not signed", name)),
+        Verification::UntrustedKey { key, fingerprint } => warn(
+            "ADD",
+            &format!(
+                "{} is signed by {} ({}), which is not trusted",
+                name, key, fingerprint
+            ),
+        ),
+    }
+    Ok(())
+}
+
+/// [`check_signature`] for packages that are re-read routinely: unsigned and
+/// untrusted packages only fail under `require`, without the warnings, but a
+/// signature that does not match still fails.
+pub fn check_signature_quietly(package: &Path, policy: SignaturePolicy) -> Result<()> {
+    match policy {
+        SignaturePolicy::Off => Ok(()),
+        SignaturePolicy::Require => check_signature(package, policy),
+        SignaturePolicy::Warn => {
+            let trusted = signing::load_trusted(&config::trusted_keys_dir())?;
+            if let Verification::Invalid { reason } = signing::verify_file(package, &trusted)? {
+                bail!("Refusing {}: {}", package.display(), reason);
             }
+            Ok(())
+        }
+    }
+}
+
+/// A template read from a `.tpl` file or out of a `.pkg`.
+pub struct SourceTemplate {
+    /// Entry name inside the package, `None` for a plain `.tpl`
+    pub entry: Option<String>,
+    /// The package from its `package.toml`, if it has one
+    pub package: Option<PackageRef>,
+    pub template: Template,
+}
+
+impl SourceTemplate {
+    pub fn provenance(&self, source: &Path, checksum: &str, added_at: i64) -> Provenance {
+        Provenance {
+            source: source.to_path_buf(),
+            entry: self.entry.clone(),
+            checksum: checksum.to_string(),
+            added_at,
+            package: self.package.clone(),
         }
+    }
+}
+
+/// Parse a `.tpl` file, or every template inside a `.pkg` after checking it
+/// against the package manifest.
+pub fn read_source(path: &Path) -> Result<Vec<SourceTemplate>> {
+    if path.extension().is_some_and(|ext| ext == "pkg") {
+        let package = packager::read_package(path)?;
+        let package_ref = package.manifest.map(|m| PackageRef {
+            name: m.package.name,
+            version: m.package.version,
+        });
+        Ok(package
+            .templates
+            .into_iter()
+            .map(|(entry, template)| SourceTemplate {
+                entry: Some(entry),
+                package: package_ref.clone(),
+                template,
+            })
+            .collect())
     } else {
         let content = fs::read_to_string(path)?;
-        let tpl: Template = toml::from_str(&content)
+        let template: Template = toml::from_str(&content)
             .with_context(|| format!("Failed to parse template: {:?}", path))?;
+        Ok(vec![SourceTemplate {
+            entry: None,
+            package: None,
+            template,
+        }])
+    }
+}
 
-        info(
-            "ADD",
-            &format!("added {} v{}", tpl.manifest.name, tpl.manifest.version),
-        );
-        let tpl_clone = tpl.clone();
-        db.add(tpl)?;
-        installed_list.push(tpl_clone);
+fn install(
+    tpl: Template,
+    provenance: Provenance,
+    db: &mut Store,
+    installed: &mut Vec<Template>,
+) -> Result<()> {
+    let conflicts = db.conflicts_with(&tpl);
+    if !conflicts.is_empty() {
+        for conflict in &conflicts {
+            error(
+                "ADD",
+                &format!(
+                    "{} is also written by {}",
+                    conflict.path.display(),
+                    conflict
+                        .templates
+                        .iter()
+                        .filter(|n| **n != tpl.manifest.name)
+                        .cloned()
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+            );
+        }
+        return Err(anyhow!(
+            "Refusing to add {}: {} conflicting target(s); list the other template in `overrides` to take precedence",
+            tpl.manifest.name,
+            conflicts.len()
+        ));
     }
-    Ok(installed_list)
+
+    info(
+        "ADD",
+        &format!("added {} v{}", tpl.manifest.name, tpl.manifest.version),
+    );
+    installed.push(tpl.clone());
+    db.set_provenance(&tpl.manifest.name, provenance);
+    db.add(tpl)
+}
+
+/// Report unmet `depends_on` entries and refuse additions that create a cycle.
+pub fn check_requires(installed: &[Template], db: &Store, scope: &str) -> Result<()> {
+    for tpl in installed {
+        let missing = resolver::missing_dependencies(tpl, |name| db.contains(name));
+        if !missing.is_empty() {
+            warn(
+                scope,
+                &format!(
+                    "{} requires {} (not installed)",
+                    tpl.manifest.name,
+                    missing.join(", ")
+                ),
+            );
+        }
+    }
+
+    db.apply_order()?;
+    Ok(())
 }
@@ -1,58 +1,388 @@
-use super::super::logging::{info, warn};
+use super::super::exit::{self, Failure};
+use super::super::logging::{error, info, warn};
+use super::super::output::{self, OutputFormat};
 use crate::config::Config;
-use crate::db::Store;
-use crate::processor;
+use crate::db::{Store, WriteRecord};
+use crate::lint;
+use crate::processor::{self, ApplyOptions, HookOutcome, WrittenFile};
+use crate::resolver;
 use anyhow::Result;
+use colored::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::time::Instant;
 
-pub fn execute(db: &Store, config: &Config, force: bool) -> Result<()> {
-    let templates = db.list();
-    if templates.is_empty() {
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Applied,
+    Skipped,
+    Failed,
+}
+
+/// Outcome of one template in an apply run.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TemplateResult {
+    pub name: String,
+    pub version: String,
+    pub status: Status,
+    /// Why the template was skipped or failed.
+    pub reason: Option<String>,
+    pub written: Vec<PathBuf>,
+    pub hook: Option<HookOutcome>,
+    pub duration_ms: u64,
+}
+
+/// Everything an apply run did, as printed by `apply --format json`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ApplyReport {
+    /// No template failed or was held back, and every hook exited successfully.
+    pub success: bool,
+    pub applied: usize,
+    pub skipped: usize,
+    pub failed: usize,
+    pub hook_failures: usize,
+    /// Reload hooks not run because they are not approved (`hyprink trust`).
+    pub hooks_unapproved: usize,
+    /// Templates skipped because a target was edited since the last apply.
+    pub modified: usize,
+    /// What the process exits with; see [`ApplyReport::exit_code`].
+    pub exit_code: i32,
+    pub duration_ms: u64,
+    pub templates: Vec<TemplateResult>,
+}
+
+impl ApplyReport {
+    /// 0 on success, otherwise the most severe of [`exit::RENDER_FAILED`],
+    /// [`exit::HOOK_FAILED`] and [`exit::HELD_BACK`]. Unapproved hooks count
+    /// as held back, not as failed.
+    pub fn exit_code(&self) -> i32 {
+        if self.failed > 0 {
+            exit::RENDER_FAILED
+        } else if self.hook_failures > 0 {
+            exit::HOOK_FAILED
+        } else if self.held_back() > 0 || self.hooks_unapproved > 0 {
+            exit::HELD_BACK
+        } else {
+            0
+        }
+    }
+
+    /// Templates skipped for a reason other than being disabled.
+    fn held_back(&self) -> usize {
+        self.templates
+            .iter()
+            .filter(|t| t.status == Status::Skipped && t.reason.as_deref() != Some("disabled"))
+            .count()
+    }
+
+    /// An error carrying the exit code, if the run did not fully succeed.
+    pub fn check(&self) -> Result<()> {
+        let code = self.exit_code();
+        if code == 0 {
+            return Ok(());
+        }
+        let mut problems = Vec::new();
+        if self.failed > 0 {
+            problems.push(format!("{} template(s) failed", self.failed));
+        }
+        if self.hook_failures > 0 {
+            problems.push(format!("{} hook(s) failed", self.hook_failures));
+        }
+        if self.held_back() > 0 {
+            problems.push(format!("{} template(s) held back", self.held_back()));
+        }
+        if self.hooks_unapproved > 0 {
+            problems.push(format!("{} hook(s) not approved", self.hooks_unapproved));
+        }
+        Err(Failure::new(code, format!("apply incomplete: {}", problems.join(", "))).into())
+    }
+}
+
+pub fn execute(
+    db: &mut Store,
+    config: &Config,
+    opts: &ApplyOptions,
+    format: OutputFormat,
+    only: Option<&[String]>,
+) -> Result<()> {
+    let report = perform(db, config, opts, only)?;
+    print(&report, format)?;
+    report.check()
+}
+
+/// Apply and record what was written, without printing anything. Templates
+/// that fail are reported rather than returned as an error.
+pub fn perform(
+    db: &mut Store,
+    config: &Config,
+    opts: &ApplyOptions,
+    only: Option<&[String]>,
+) -> Result<ApplyReport> {
+    let opts = ApplyOptions {
+        last_written: db.written_checksums(),
+        approved_hooks: db.approved_hooks().clone(),
+        ..opts.clone()
+    };
+    let (report, written) = run(db, config, &opts, only)?;
+
+    if opts.policy.staging().is_none() && !written.is_empty() {
+        for (name, files) in &written {
+            record(db, name, files);
+        }
+        db.save()?;
+    }
+    Ok(report)
+}
+
+/// Print the JSON document, or the summary table and closing line in text mode.
+pub fn print(report: &ApplyReport, format: OutputFormat) -> Result<()> {
+    if format.is_json() {
+        output::print_json("apply", report)?;
+    } else if report.templates.is_empty() {
         info("APPLY", "No templates to apply");
-        return Ok(());
+    } else if !report.success {
+        print_summary(report);
+        let mut msg = format!(
+            "applied {} templates ({} skipped, {} failed, {} hooks failed",
+            report.applied, report.skipped, report.failed, report.hook_failures
+        );
+        if report.hooks_unapproved > 0 {
+            msg.push_str(&format!(", {} hooks not approved", report.hooks_unapproved));
+        }
+        msg.push(')');
+        warn("APPLY", &msg);
+    } else {
+        print_summary(report);
+        info(
+            "APPLY",
+            &format!(
+                "applied {} templates ({} skipped)",
+                report.applied, report.skipped
+            ),
+        );
     }
+    Ok(())
+}
 
-    let total = templates.len();
-    let mut hook_failures = 0;
-    let mut skipped = 0;
+/// One row per template, with the reason for anything that was not applied.
+fn print_summary(report: &ApplyReport) {
+    let width = report
+        .templates
+        .iter()
+        .map(|t| t.name.len())
+        .max()
+        .unwrap_or(0)
+        .max("TEMPLATE".len());
 
-    for tpl in templates {
-        if tpl.manifest.ignored {
-            info(
-                "APPLY",
+    println!();
+    println!(
+        "  {}",
+        format!(
+            "{:<width$}  {:<8}  {:>7}  DETAIL",
+            "TEMPLATE", "STATUS", "TIME"
+        )
+        .bold()
+    );
+    for tpl in &report.templates {
+        let status = match tpl.status {
+            Status::Applied => format!("{:<8}", "applied").green(),
+            Status::Skipped => format!("{:<8}", "skipped").yellow(),
+            Status::Failed => format!("{:<8}", "failed").red().bold(),
+        };
+        let detail = match (&tpl.reason, &tpl.hook) {
+            (Some(reason), _) => reason.clone(),
+            (None, Some(hook)) if !hook.approved => format!(
+                "hook not approved (run `hyprink trust {}`): {}",
+                tpl.name, hook.command
+            ),
+            (None, Some(hook)) if !hook.success => format!(
+                "hook failed (exit {}): {}",
+                hook.exit_code.unwrap_or(-1),
+                hook.command
+            ),
+            _ => String::new(),
+        };
+        println!(
+            "  {:<width$}  {}  {:>7}  {}",
+            tpl.name,
+            status,
+            format!("{}ms", tpl.duration_ms),
+            detail.dimmed()
+        );
+    }
+    println!();
+}
+
+/// One line per template, for reports produced elsewhere (e.g. by the daemon).
+pub fn print_templates(report: &ApplyReport) {
+    for tpl in &report.templates {
+        let line = match &tpl.reason {
+            Some(reason) => format!("{}: {}", tpl.name, reason),
+            None => tpl.name.clone(),
+        };
+        match tpl.status {
+            Status::Applied => info("APPLY", &format!("applied <primary>{}</primary>", line)),
+            Status::Skipped => warn("APPLY", &format!("skipped {}", line)),
+            Status::Failed => error("APPLY", &format!("failed {}", line)),
+        }
+        // Unapproved hooks are left to the summary, as in a local apply
+        if let Some(hook) = tpl.hook.as_ref().filter(|h| h.approved && !h.success) {
+            error(
+                "HOOK",
                 &format!(
-                    "ignoring <secondary>{}</secondary> (disabled)",
-                    tpl.manifest.name
+                    "[{}] failed (exit {}): {}",
+                    tpl.name,
+                    hook.exit_code.unwrap_or(-1),
+                    hook.command
                 ),
             );
-            skipped += 1;
-            continue;
         }
+    }
+}
 
-        info(
-            "APPLY",
-            &format!("applying <primary>{}</primary>", tpl.manifest.name),
+/// Remember what was written so `status` and later applies can spot hand edits.
+pub fn record(db: &mut Store, name: &str, files: &[WrittenFile]) {
+    let now = chrono::Utc::now().timestamp();
+    for file in files {
+        db.record_write(
+            &file.path,
+            WriteRecord {
+                template: name.to_string(),
+                target: file.target.clone(),
+                checksum: file.checksum.clone(),
+                written_at: now,
+            },
         );
-        if !processor::apply(tpl, config, force)? {
-            hook_failures += 1;
-        }
     }
+}
+
+type Written = Vec<(String, Vec<WrittenFile>)>;
 
-    let applied = total - skipped;
+/// Apply every stored template (or just those in `only`) in order. A template
+/// that fails is recorded in the report and the run carries on with the next.
+fn run(
+    db: &Store,
+    config: &Config,
+    opts: &ApplyOptions,
+    only: Option<&[String]>,
+) -> Result<(ApplyReport, Written)> {
+    let started = Instant::now();
+    let mut templates = db.apply_order()?;
+    if let Some(only) = only {
+        templates.retain(|t| only.contains(&t.manifest.name));
+    }
+    let mut report = ApplyReport::default();
+    let mut written = Vec::new();
 
-    if hook_failures > 0 {
-        warn(
+    let mut conflicted = HashSet::new();
+    for conflict in db.conflicts() {
+        error(
             "APPLY",
             &format!(
-                "applied {} templates ({} skipped) but {} hooks failed",
-                applied, skipped, hook_failures
+                "conflict: {} is written by {}",
+                conflict.path.display(),
+                conflict.templates.join(", ")
             ),
         );
-    } else {
-        info(
-            "APPLY",
-            &format!("applied {} templates ({} skipped)", applied, skipped),
-        );
+        conflicted.extend(conflict.templates);
     }
 
-    Ok(())
+    for tpl in templates {
+        let tpl_started = Instant::now();
+        let mut result = TemplateResult {
+            name: tpl.manifest.name.clone(),
+            version: tpl.manifest.version.clone(),
+            status: Status::Skipped,
+            reason: None,
+            written: Vec::new(),
+            hook: None,
+            duration_ms: 0,
+        };
+
+        let missing = resolver::missing_dependencies(tpl, |name| db.contains(name));
+
+        if tpl.manifest.ignored {
+            info(
+                "APPLY",
+                &format!(
+                    "ignoring <secondary>{}</secondary> (disabled)",
+                    tpl.manifest.name
+                ),
+            );
+            result.reason = Some("disabled".to_string());
+        } else if conflicted.contains(&tpl.manifest.name) {
+            error(
+                "APPLY",
+                &format!("skipping {}: conflicting targets", tpl.manifest.name),
+            );
+            result.reason = Some("conflicting targets".to_string());
+        } else if !missing.is_empty() {
+            let reason = format!("missing dependencies {}", missing.join(", "));
+            error(
+                "APPLY",
+                &format!("skipping {}: {}", tpl.manifest.name, reason),
+            );
+            result.reason = Some(reason);
+        } else {
+            info(
+                "APPLY",
+                &format!("applying <primary>{}</primary>", tpl.manifest.name),
+            );
+            match processor::apply(tpl, config, opts) {
+                Ok(outcome) if !outcome.modified.is_empty() => {
+                    let paths: Vec<String> = outcome
+                        .modified
+                        .iter()
+                        .map(|p| p.display().to_string())
+                        .collect();
+                    error(
+                        "APPLY",
+                        &format!(
+                            "skipping {}: {} changed on disk since the last apply (see `hyprink status --diff`, or use --force)",
+                            tpl.manifest.name,
+                            paths.join(", ")
+                        ),
+                    );
+                    report.modified += 1;
+                    result.reason = Some(format!("modified on disk: {}", paths.join(", ")));
+                }
+                Ok(outcome) => {
+                    if outcome.hook_failed() {
+                        report.hook_failures += 1;
+                    }
+                    if outcome.hook_unapproved() {
+                        report.hooks_unapproved += 1;
+                    }
+                    result.status = Status::Applied;
+                    result.written = outcome.written.iter().map(|f| f.path.clone()).collect();
+                    result.hook = outcome.hook;
+                    written.push((tpl.manifest.name.clone(), outcome.written));
+                }
+                Err(e) => {
+                    let reason = lint::describe(&*e);
+                    error(
+                        "APPLY",
+                        &format!("failed to apply {}: {}", tpl.manifest.name, reason),
+                    );
+                    result.status = Status::Failed;
+                    result.reason = Some(reason);
+                }
+            }
+        }
+
+        match result.status {
+            Status::Applied => report.applied += 1,
+            Status::Skipped => report.skipped += 1,
+            Status::Failed => report.failed += 1,
+        }
+        result.duration_ms = tpl_started.elapsed().as_millis() as u64;
+        report.templates.push(result);
+    }
+
+    report.exit_code = report.exit_code();
+    report.success = report.exit_code == 0;
+    report.duration_ms = started.elapsed().as_millis() as u64;
+    Ok((report, written))
 }
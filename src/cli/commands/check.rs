@@ -0,0 +1,49 @@
+use super::super::logging::info;
+use super::super::output::{self, OutputFormat};
+use super::lint::print_issue;
+use crate::config;
+use crate::lint::{self, Issue, Severity};
+use anyhow::{Result, bail};
+use serde::Serialize;
+use std::path::PathBuf;
+
+#[derive(Debug, Serialize)]
+pub struct ConfigReport {
+    pub path: PathBuf,
+    /// The config parsed and has no error-level issue.
+    pub valid: bool,
+    pub issues: Vec<Issue>,
+}
+
+/// `hyprink config check`
+pub fn config(format: OutputFormat) -> Result<()> {
+    let path = config::resolve_config_path();
+    let issues = lint::check_config_file(&path);
+    let report = ConfigReport {
+        valid: !lint::has_errors(&issues),
+        path,
+        issues,
+    };
+
+    if format.is_json() {
+        output::print_json("config check", &report)?;
+    } else {
+        println!("  {}", report.path.display());
+        for issue in &report.issues {
+            print_issue(issue);
+        }
+        if report.valid {
+            let warnings = report
+                .issues
+                .iter()
+                .filter(|i| i.severity == Severity::Warning)
+                .count();
+            info("CONFIG", &format!("config ok ({} warnings)", warnings));
+        }
+    }
+
+    if !report.valid {
+        bail!("config check failed for {}", report.path.display());
+    }
+    Ok(())
+}
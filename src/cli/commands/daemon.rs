@@ -0,0 +1,459 @@
+use super::super::args::{Commands, DaemonCommands, GlobalArgs};
+use super::super::cli_config::CliConfig;
+use super::super::lock::{self, LockMode, Wait};
+use super::super::logging::{debug, info, warn};
+use super::super::output::OutputFormat;
+use super::super::rpc::{self, Client, Event, Request, Response, RpcError};
+use super::apply::{self, ApplyReport};
+use super::{list, status, theme};
+use crate::config::{self, Config, PathsSection};
+use crate::db::Store;
+use crate::logger;
+use crate::paths::PathPolicy;
+use crate::processor::ApplyOptions;
+use crate::themes;
+use anyhow::{Context, Result, anyhow, bail};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::fs;
+use std::io::{BufRead, BufReader};
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::SystemTime;
+
+pub fn execute(
+    command: Option<DaemonCommands>,
+    db_path: &Path,
+    format: OutputFormat,
+) -> Result<()> {
+    match command {
+        None => serve(db_path),
+        Some(DaemonCommands::Theme { file }) => {
+            let file = file
+                .map(|f| fs::canonicalize(&f).with_context(|| format!("Theme not found: {:?}", f)))
+                .transpose()?;
+            let report: ApplyReport =
+                serde_json::from_value(connect()?.call("theme", json!({ "path": file }))?)?;
+            finish_apply(&report, format)
+        }
+        Some(DaemonCommands::Events) => {
+            let mut client = connect()?;
+            client.call("subscribe", Value::Null)?;
+            while let Some(event) = client.next_event()? {
+                println!("{}", serde_json::to_string(&event)?);
+            }
+            Ok(())
+        }
+        Some(DaemonCommands::Stop) => {
+            connect()?.call("shutdown", Value::Null)?;
+            info("DAEMON", "daemon stopped");
+            Ok(())
+        }
+    }
+}
+
+/// Hand `apply`, `list` and `status` to a running daemon. Returns false when
+/// the command should run locally.
+pub fn forward(cmd: &Commands, global: &GlobalArgs) -> Result<bool> {
+    let policy =
+        PathPolicy::from_config(&PathsSection::default()).with_staging(global.root.clone());
+    if !forwardable(cmd, &policy) {
+        return Ok(false);
+    }
+    let Some(mut client) = Client::connect() else {
+        return Ok(false);
+    };
+
+    let format = global.output_format();
+    logger::set_quiet(format.is_json());
+    debug("DAEMON", "forwarding to the running daemon");
+
+    match cmd {
+        Commands::Apply {
+            toggle_force,
+            force,
+        } => {
+            let force = CliConfig::resolve_force(*toggle_force, *force);
+            let report: ApplyReport =
+                serde_json::from_value(client.call("apply", json!({ "force": force }))?)?;
+            finish_apply(&report, format)?;
+        }
+        Commands::List { .. } => {
+            let listing = serde_json::from_value(client.call("list", Value::Null)?)?;
+            list::print(&listing, format)?;
+        }
+        Commands::Status { name, .. } => {
+            let report = serde_json::from_value(client.call("status", json!({ "name": name }))?)?;
+            status::print(&report, false, format)?;
+        }
+        _ => unreachable!("checked above"),
+    }
+    Ok(true)
+}
+
+/// Whether the daemon can run `cmd`. It has its own path policy, so runs
+/// staged with `--root` or `HYPRINK_ROOT` stay local.
+fn forwardable(cmd: &Commands, policy: &PathPolicy) -> bool {
+    if policy.staging().is_some() {
+        return false;
+    }
+    match cmd {
+        Commands::Apply { .. } | Commands::List { command: None } => true,
+        // Diffs need the rendered output, which the daemon does not send
+        Commands::Status { diff, .. } => !diff,
+        _ => false,
+    }
+}
+
+fn finish_apply(report: &ApplyReport, format: OutputFormat) -> Result<()> {
+    if !format.is_json() {
+        apply::print_templates(report);
+    }
+    apply::print(report, format)?;
+    report.check()
+}
+
+fn connect() -> Result<Client> {
+    Client::connect().ok_or_else(|| {
+        anyhow!(
+            "no daemon is listening on {} (start one with `hyprink daemon`)",
+            rpc::socket_path().display()
+        )
+    })
+}
+
+// === Server ===
+
+/// Store and config as the daemon currently sees them.
+struct State {
+    db_path: PathBuf,
+    db: Store,
+    config: Config,
+    /// File whose `[theme]` table is laid over the configured theme
+    theme: Option<PathBuf>,
+    store_mtime: Option<SystemTime>,
+    /// Modification times of the config and the theme selection
+    config_stamp: Vec<Option<SystemTime>>,
+}
+
+impl State {
+    fn load(db_path: &Path) -> Result<Self> {
+        Ok(State {
+            db_path: db_path.to_path_buf(),
+            db: Store::load(db_path)?,
+            config: load_config(None, false)?,
+            theme: None,
+            store_mtime: mtime(db_path),
+            config_stamp: config_stamp(),
+        })
+    }
+
+    /// Re-read the store and config if they changed on disk, e.g. after a local `add`.
+    fn refresh(&mut self) -> Result<()> {
+        let store_mtime = mtime(&self.db_path);
+        if store_mtime != self.store_mtime {
+            debug("DAEMON", "store changed on disk, reloading");
+            self.db = Store::load(&self.db_path)?;
+            self.store_mtime = store_mtime;
+        }
+        let config_stamp = config_stamp();
+        if config_stamp != self.config_stamp {
+            debug(
+                "DAEMON",
+                "config or selected theme changed on disk, reloading",
+            );
+            self.config = load_config(self.theme.as_deref(), false)?;
+            self.config_stamp = config_stamp;
+        }
+        Ok(())
+    }
+
+    fn set_theme(&mut self, theme: Option<PathBuf>) -> Result<()> {
+        self.config = load_config(theme.as_deref(), false)?;
+        self.theme = theme;
+        Ok(())
+    }
+
+    fn apply(&mut self, force: bool, only: Option<&[String]>) -> Result<ApplyReport> {
+        // Locked before reloading, so a local `add` cannot save the store in
+        // between and then be overwritten with what was read before it
+        let _lock = lock::acquire(LockMode::Exclusive, Wait::Forever)?;
+        self.refresh()?;
+        let fresh;
+        let config = if force {
+            fresh = load_config(self.theme.as_deref(), true)?;
+            &fresh
+        } else {
+            &self.config
+        };
+
+        let opts = ApplyOptions {
+            force,
+            policy: PathPolicy::from_config(&config.paths),
+            ..Default::default()
+        };
+        let report = apply::perform(&mut self.db, config, &opts, only)?;
+        self.store_mtime = mtime(&self.db_path);
+        Ok(report)
+    }
+}
+
+fn load_config(theme: Option<&Path>, no_cache: bool) -> Result<Config> {
+    let mut config = if no_cache {
+        Config::load_no_cache()
+    } else {
+        Config::load()
+    }
+    .context("Failed to load hyprink config")?;
+    theme::apply_selected(&mut config);
+    if let Some(theme) = theme {
+        config
+            .overlay_theme(theme)
+            .with_context(|| format!("Failed to load theme from {}", theme.display()))?;
+    }
+    Ok(config)
+}
+
+/// Changes when `hyprink.conf` is edited or `hyprink theme use` picks
+/// another theme.
+fn config_stamp() -> Vec<Option<SystemTime>> {
+    std::iter::once(config::resolve_config_path())
+        .chain(themes::selection_files(&config::themes_dir()))
+        .map(|path| mtime(&path))
+        .collect()
+}
+
+fn mtime(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+type Conn = Arc<Mutex<UnixStream>>;
+
+/// Events a subscriber may fall behind by before it is dropped.
+const EVENT_QUEUE: usize = 64;
+
+struct Shared {
+    state: Mutex<State>,
+    /// One queue per subscriber, drained by its own writer thread
+    subscribers: Mutex<Vec<SyncSender<Arc<Event>>>>,
+}
+
+impl Shared {
+    /// Queue `event` for every subscriber. Subscribers that are gone or too
+    /// slow to keep up are dropped rather than holding up everyone else.
+    fn broadcast<T: Serialize>(&self, event: &str, data: &T) {
+        let event = Arc::new(Event {
+            event: event.to_string(),
+            data: serde_json::to_value(data).unwrap_or(Value::Null),
+        });
+        self.subscribers
+            .lock()
+            .unwrap()
+            .retain(|queue| queue.try_send(Arc::clone(&event)).is_ok());
+    }
+
+    fn subscribe(&self, conn: &Conn) {
+        let (queue, events) = mpsc::sync_channel::<Arc<Event>>(EVENT_QUEUE);
+        let conn = Arc::clone(conn);
+        thread::spawn(move || {
+            for event in events {
+                if rpc::send(&mut conn.lock().unwrap(), &*event).is_err() {
+                    break;
+                }
+            }
+        });
+        self.subscribers.lock().unwrap().push(queue);
+    }
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct ApplyParams {
+    force: bool,
+    templates: Option<Vec<String>>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct StatusParams {
+    name: Option<String>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct ThemeParams {
+    path: Option<PathBuf>,
+}
+
+fn serve(db_path: &Path) -> Result<()> {
+    let path = rpc::socket_path();
+    if UnixStream::connect(&path).is_ok() {
+        bail!("a daemon is already listening on {}", path.display());
+    }
+    // Only a stale socket is ours to replace
+    if let Ok(meta) = fs::symlink_metadata(&path) {
+        if !meta.file_type().is_socket() {
+            bail!("{} exists and is not a socket", path.display());
+        }
+        fs::remove_file(&path)?;
+    }
+
+    // Created 0600 from the start; chmod after binding would leave a window
+    // in a shared fallback directory like /tmp
+    let umask = unsafe { libc::umask(0o177) };
+    let listener = UnixListener::bind(&path);
+    unsafe { libc::umask(umask) };
+    let listener = listener.with_context(|| format!("Failed to bind {}", path.display()))?;
+
+    let shared = Arc::new(Shared {
+        state: Mutex::new(State::load(db_path)?),
+        subscribers: Mutex::new(Vec::new()),
+    });
+    info("DAEMON", &format!("listening on {}", path.display()));
+
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                let shared = Arc::clone(&shared);
+                thread::spawn(move || handle_connection(stream, &shared));
+            }
+            Err(e) => warn("DAEMON", &format!("failed to accept connection: {}", e)),
+        }
+    }
+    Ok(())
+}
+
+fn handle_connection(stream: UnixStream, shared: &Shared) {
+    let Ok(reader) = stream.try_clone() else {
+        return;
+    };
+    let conn: Conn = Arc::new(Mutex::new(stream));
+
+    for line in BufReader::new(reader).lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = match serde_json::from_str::<Request>(&line) {
+            Ok(req) => {
+                let id = req.id;
+                match dispatch(req, shared, &conn) {
+                    Ok(result) => Response {
+                        id,
+                        result: Some(result),
+                        error: None,
+                    },
+                    Err(e) => Response {
+                        id,
+                        result: None,
+                        error: Some(RpcError {
+                            message: format!("{:#}", e),
+                        }),
+                    },
+                }
+            }
+            Err(e) => Response {
+                id: 0,
+                result: None,
+                error: Some(RpcError {
+                    message: format!("malformed request: {}", e),
+                }),
+            },
+        };
+        if rpc::send(&mut conn.lock().unwrap(), &response).is_err() {
+            break;
+        }
+    }
+}
+
+fn dispatch(req: Request, shared: &Shared, conn: &Conn) -> Result<Value> {
+    debug("DAEMON", &format!("request {}: {}", req.id, req.method));
+    let params = if req.params.is_null() {
+        json!({})
+    } else {
+        req.params
+    };
+
+    match req.method.as_str() {
+        "ping" => Ok(json!({ "version": env!("CARGO_PKG_VERSION") })),
+        "apply" => {
+            let params: ApplyParams = serde_json::from_value(params)?;
+            let report = shared
+                .state
+                .lock()
+                .unwrap()
+                .apply(params.force, params.templates.as_deref())?;
+            shared.broadcast("applied", &report);
+            Ok(serde_json::to_value(report)?)
+        }
+        "theme" => {
+            let params: ThemeParams = serde_json::from_value(params)?;
+            let mut state = shared.state.lock().unwrap();
+            state.set_theme(params.path.clone())?;
+            info(
+                "DAEMON",
+                &match &params.path {
+                    Some(path) => format!("theme switched to {}", path.display()),
+                    None => "theme reset to hyprink.conf".to_string(),
+                },
+            );
+            shared.broadcast("theme", &json!({ "path": params.path }));
+            let report = state.apply(false, None)?;
+            drop(state);
+            shared.broadcast("applied", &report);
+            Ok(serde_json::to_value(report)?)
+        }
+        "list" => {
+            let mut state = shared.state.lock().unwrap();
+            state.refresh()?;
+            Ok(serde_json::to_value(list::listing(&state.db))?)
+        }
+        "status" => {
+            let params: StatusParams = serde_json::from_value(params)?;
+            let mut state = shared.state.lock().unwrap();
+            state.refresh()?;
+            let report = status::report(params.name.as_deref(), &state.db, &state.config)?;
+            Ok(serde_json::to_value(report)?)
+        }
+        "subscribe" => {
+            shared.subscribe(conn);
+            Ok(json!({ "subscribed": true }))
+        }
+        "shutdown" => {
+            let response = Response {
+                id: req.id,
+                result: Some(json!({ "stopping": true })),
+                error: None,
+            };
+            let _ = rpc::send(&mut conn.lock().unwrap(), &response);
+            let _ = fs::remove_file(rpc::socket_path());
+            info("DAEMON", "shutting down");
+            std::process::exit(0);
+        }
+        other => Err(anyhow!("unknown method '{}'", other)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_staged_runs_are_not_forwarded() {
+        let apply = Commands::Apply {
+            toggle_force: false,
+            force: false,
+        };
+        let live = PathPolicy::default();
+        let staged = PathPolicy::default().with_staging(Some(PathBuf::from("/tmp/stage")));
+        assert!(forwardable(&apply, &live));
+        assert!(!forwardable(&apply, &staged));
+        assert!(!forwardable(&Commands::Compile, &live));
+    }
+}
@@ -0,0 +1,206 @@
+use super::super::exit::{self, Failure};
+use super::super::logging::{error, info, warn};
+use super::{add, trust, update};
+use crate::config::SignaturePolicy;
+use crate::db::{Provenance, Store};
+use crate::packager::{self, PackageInfo};
+use crate::template::Template;
+use anyhow::{Context, Result, anyhow};
+use colored::*;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+/// A template as written by `store export`: the template itself plus the
+/// provenance recorded for it. Plain `add` ignores the extra table.
+#[derive(Serialize, Deserialize)]
+struct ExportedTemplate {
+    #[serde(flatten)]
+    template: Template,
+    provenance: Option<Provenance>,
+}
+
+/// Write the store (or the named templates) to a directory of .tpl files,
+/// or to a single .pkg when `dest` ends in `.pkg`.
+pub fn export(dest: &Path, names: &[String], db: &Store) -> Result<()> {
+    let templates: Vec<&Template> = if names.is_empty() {
+        db.list()
+    } else {
+        names
+            .iter()
+            .map(|n| {
+                db.get(n)
+                    .ok_or_else(|| anyhow!("template '{}' not found", n))
+            })
+            .collect::<Result<_>>()?
+    };
+    if templates.is_empty() {
+        info("STORE", "No templates to export");
+        return Ok(());
+    }
+
+    let mut entries = Vec::with_capacity(templates.len());
+    for tpl in &templates {
+        let exported = ExportedTemplate {
+            template: (*tpl).clone(),
+            provenance: db.provenance(&tpl.manifest.name).cloned(),
+        };
+        let content = toml::to_string_pretty(&exported)
+            .with_context(|| format!("Failed to serialize {}", tpl.manifest.name))?;
+        entries.push((file_name(&tpl.manifest.name), content.into_bytes()));
+    }
+
+    if dest.extension().is_some_and(|ext| ext == "pkg") {
+        let stem = dest.file_stem().unwrap_or_default().to_string_lossy();
+        let manifest = packager::manifest_for(PackageInfo::new(&stem, "0.1.0"), &entries)?;
+        packager::write_package(dest, &manifest, &entries)?;
+    } else {
+        fs::create_dir_all(dest)
+            .with_context(|| format!("Failed to create export directory {:?}", dest))?;
+        for (name, content) in &entries {
+            fs::write(dest.join(name), content)?;
+        }
+    }
+
+    info(
+        "STORE",
+        &format!("exported {} templates to {}", entries.len(), dest.display()),
+    );
+    Ok(())
+}
+
+/// Merge an export (directory of .tpl files or .pkg) into the store.
+/// Templates that differ from a stored one of the same name are only
+/// replaced with `overwrite`.
+pub fn import(
+    source: &Path,
+    overwrite: bool,
+    db: &mut Store,
+    signatures: SignaturePolicy,
+) -> Result<()> {
+    let entries = read_export(source, signatures)?;
+
+    let mut added = 0;
+    let mut replaced = 0;
+    let mut unchanged = 0;
+    let mut conflicts = 0;
+    let mut failed = 0;
+    let mut imported = Vec::new();
+
+    for (entry, content) in entries {
+        let exported: ExportedTemplate = match toml::from_str(&content) {
+            Ok(exported) => exported,
+            Err(e) => {
+                error("STORE", &format!("{}: {}", entry, e));
+                failed += 1;
+                continue;
+            }
+        };
+        let tpl = exported.template;
+        let name = tpl.manifest.name.clone();
+
+        let existing = db.get(&name);
+        if existing.is_some_and(|stored| update::same_content(stored, &tpl)) {
+            unchanged += 1;
+            continue;
+        }
+        if existing.is_some() && !overwrite {
+            println!("  {} {} (differs from stored)", "!".yellow().bold(), name);
+            conflicts += 1;
+            continue;
+        }
+        if let Some(conflict) = db.conflicts_with(&tpl).first() {
+            println!(
+                "  {} {} ({} is also written by {})",
+                "!".yellow().bold(),
+                name,
+                conflict.path.display(),
+                conflict.templates.join(", ")
+            );
+            conflicts += 1;
+            continue;
+        }
+
+        let is_new = existing.is_none();
+        trust::warn_unapproved(&tpl, db, "STORE");
+        imported.push(tpl.clone());
+        db.add(tpl)?;
+        if let Some(provenance) = exported.provenance {
+            db.set_provenance(&name, provenance);
+        }
+        if is_new {
+            println!("  {} {}", "+".green().bold(), name);
+            added += 1;
+        } else {
+            println!("  {} {}", "~".yellow().bold(), name);
+            replaced += 1;
+        }
+    }
+
+    if added + replaced > 0 {
+        add::check_requires(&imported, db, "STORE")?;
+        db.save()?;
+    }
+
+    let mut msg = format!(
+        "imported {} new, {} replaced, {} unchanged",
+        added, replaced, unchanged
+    );
+    if conflicts > 0 {
+        msg = format!(
+            "{} ({} conflicts, use --overwrite to replace)",
+            msg, conflicts
+        );
+    }
+    if conflicts > 0 {
+        warn("STORE", &msg);
+    } else {
+        info("STORE", &msg);
+    }
+    if failed > 0 {
+        return Err(Failure::new(
+            exit::FAILURE,
+            format!("import incomplete: {} entries failed to parse", failed),
+        )
+        .into());
+    }
+    Ok(())
+}
+
+/// Raw `.tpl` text from an export directory, or from a `.pkg` once it passed
+/// the same signature and checksum checks as `add`.
+fn read_export(source: &Path, signatures: SignaturePolicy) -> Result<Vec<(String, String)>> {
+    if source.extension().is_some_and(|ext| ext == "pkg") {
+        add::check_signature(source, signatures)?;
+        packager::read_package(source)?;
+        return packager::read_entries(source);
+    }
+    if !source.is_dir() {
+        return Err(anyhow!("Not a directory or .pkg: {:?}", source));
+    }
+
+    let mut entries = Vec::new();
+    for entry in fs::read_dir(source)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == "tpl") {
+            let name = path.file_name().unwrap().to_string_lossy().to_string();
+            entries.push((name, fs::read_to_string(&path)?));
+        }
+    }
+    entries.sort();
+    Ok(entries)
+}
+
+fn file_name(template: &str) -> String {
+    let safe: String = template
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '-' || c == '_' || c == '.' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("{}.tpl", safe)
+}
@@ -0,0 +1,45 @@
+use super::super::logging::info;
+use crate::config;
+use crate::signing::{self, KeyPair};
+use anyhow::{Result, bail};
+use std::fs;
+
+pub fn execute(name: Option<String>, force: bool) -> Result<()> {
+    let name = name
+        .or_else(|| std::env::var("USER").ok())
+        .unwrap_or_else(|| "default".to_string());
+    if name.is_empty() || name.contains(['/', '\0']) || name.starts_with('.') {
+        bail!("invalid key name '{}'", name);
+    }
+
+    let dir = config::keys_dir();
+    let existing = dir.join(format!("{}.{}", name, signing::SECRET_EXT));
+    if existing.exists() && !force {
+        bail!(
+            "a key named '{}' already exists at {} (use --force to replace it)",
+            name,
+            existing.display()
+        );
+    }
+
+    let keys = KeyPair::generate(&name)?;
+    let (secret, public) = keys.save(&dir)?;
+
+    // Packages we sign ourselves should install without further setup
+    let trusted_dir = config::trusted_keys_dir();
+    fs::create_dir_all(&trusted_dir)?;
+    let trusted = trusted_dir.join(public.file_name().unwrap_or_default());
+    fs::copy(&public, &trusted)?;
+
+    info(
+        "KEYGEN",
+        &format!("created key {} ({})", name, keys.public().fingerprint()),
+    );
+    info("KEYGEN", &format!("secret key: {}", secret.display()));
+    info(
+        "KEYGEN",
+        &format!("public key: {} (share this one)", public.display()),
+    );
+    info("KEYGEN", &format!("trusted as {}", trusted.display()));
+    Ok(())
+}
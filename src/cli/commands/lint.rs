@@ -0,0 +1,130 @@
+use super::super::logging::info;
+use super::super::output::{self, OutputFormat};
+use super::add;
+use crate::config::Config;
+use crate::db::Store;
+use crate::lint::{self, Issue, Severity};
+use crate::paths::PathPolicy;
+use crate::resolver;
+use crate::template::Template;
+use anyhow::{Result, anyhow, bail};
+use colored::*;
+use serde::Serialize;
+use std::path::Path;
+
+#[derive(Debug, Serialize)]
+pub struct LintedTemplate {
+    pub name: String,
+    pub issues: Vec<Issue>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct LintReport {
+    /// No template has an error-level issue.
+    pub success: bool,
+    pub errors: usize,
+    pub warnings: usize,
+    pub templates: Vec<LintedTemplate>,
+}
+
+pub fn execute(
+    target: Option<String>,
+    db: &Store,
+    config: &Config,
+    policy: &PathPolicy,
+    format: OutputFormat,
+) -> Result<()> {
+    let templates = select(target.as_deref(), db)?;
+    let mut report = LintReport::default();
+
+    for tpl in &templates {
+        let mut issues = lint::lint_template(tpl, config, policy);
+        let missing = resolver::missing_dependencies(tpl, |name| {
+            db.contains(name) || templates.iter().any(|t| t.manifest.name == name)
+        });
+        if !missing.is_empty() {
+            issues.push(Issue {
+                severity: Severity::Warning,
+                location: None,
+                message: format!("missing dependencies {}", missing.join(", ")),
+            });
+        }
+        for issue in &issues {
+            match issue.severity {
+                Severity::Error => report.errors += 1,
+                Severity::Warning => report.warnings += 1,
+            }
+        }
+        report.templates.push(LintedTemplate {
+            name: tpl.manifest.name.clone(),
+            issues,
+        });
+    }
+    report.success = report.errors == 0;
+
+    if format.is_json() {
+        output::print_json("lint", &report)?;
+    } else {
+        print_report(&report);
+    }
+
+    if !report.success {
+        bail!("lint found {} error(s)", report.errors);
+    }
+    Ok(())
+}
+
+/// A .tpl/.pkg path if one exists under that name, otherwise a stored template.
+fn select(target: Option<&str>, db: &Store) -> Result<Vec<Template>> {
+    match target {
+        Some(t) if Path::new(t).is_file() => Ok(add::read_source(Path::new(t))?
+            .into_iter()
+            .map(|parsed| parsed.template)
+            .collect()),
+        Some(name) => db
+            .get(name)
+            .cloned()
+            .map(|tpl| vec![tpl])
+            .ok_or_else(|| anyhow!("template '{}' not found", name)),
+        None => Ok(db.list().into_iter().cloned().collect()),
+    }
+}
+
+pub fn print_issue(issue: &Issue) {
+    let label = match issue.severity {
+        Severity::Error => "error".red().bold(),
+        Severity::Warning => "warning".yellow().bold(),
+    };
+    match &issue.location {
+        Some(location) => println!("    {} {}: {}", label, location.dimmed(), issue.message),
+        None => println!("    {} {}", label, issue.message),
+    }
+}
+
+fn print_report(report: &LintReport) {
+    if report.templates.is_empty() {
+        info("LINT", "No templates to lint");
+        return;
+    }
+
+    for tpl in &report.templates {
+        if tpl.issues.is_empty() {
+            println!("  {} {}", tpl.name.blue().bold(), "ok".green());
+            continue;
+        }
+        println!("  {}", tpl.name.blue().bold());
+        for issue in &tpl.issues {
+            print_issue(issue);
+        }
+    }
+
+    info(
+        "LINT",
+        &format!(
+            "{} templates, {} errors, {} warnings",
+            report.templates.len(),
+            report.errors,
+            report.warnings
+        ),
+    );
+}
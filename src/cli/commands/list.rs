@@ -1,11 +1,47 @@
 use super::super::args::ListCommands;
 use super::super::logging::{error, info};
+use super::super::output::{self, OutputFormat};
 use crate::config::Config;
-use crate::db::Store;
+use crate::db::{PackageRef, Provenance, Store};
+use crate::paths;
 use anyhow::Result;
 use colored::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
 
-pub fn execute(command: Option<ListCommands>, db: &mut Store, _config: &Config) -> Result<()> {
+/// One entry of `list --format json`.
+#[derive(Serialize, Deserialize)]
+pub struct ListedTemplate {
+    name: String,
+    version: String,
+    description: String,
+    authors: Vec<String>,
+    ignored: bool,
+    depends_on: Vec<String>,
+    targets: Vec<ListedTarget>,
+    provenance: Option<Provenance>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ListedTarget {
+    target: String,
+    /// The target with `~` and relative paths expanded.
+    path: PathBuf,
+}
+
+/// The `list --format json` document, also served by the daemon.
+#[derive(Serialize, Deserialize)]
+pub struct Listing {
+    templates: Vec<ListedTemplate>,
+}
+
+pub fn execute(
+    command: Option<ListCommands>,
+    db: &mut Store,
+    _config: &Config,
+    format: OutputFormat,
+) -> Result<()> {
     match command {
         Some(ListCommands::Clear) => {
             let count = db.list().len();
@@ -33,31 +69,88 @@ pub fn execute(command: Option<ListCommands>, db: &mut Store, _config: &Config)
                 error("STORE", &format!("template '{}' not found", name));
             }
         }
-        None => {
-            list_store(db);
-        }
+        None => print(&listing(db), format)?,
+    }
+    Ok(())
+}
+
+/// Print a listing made here or by the daemon.
+pub fn print(listing: &Listing, format: OutputFormat) -> Result<()> {
+    if format.is_json() {
+        return output::print_json("list", listing);
     }
+    list_store(listing);
     Ok(())
 }
 
-fn list_store(db: &Store) {
+pub fn listing(db: &Store) -> Listing {
+    let templates = db
+        .list()
+        .into_iter()
+        .map(|tpl| ListedTemplate {
+            name: tpl.manifest.name.clone(),
+            version: tpl.manifest.version.clone(),
+            description: tpl.manifest.description.clone(),
+            authors: tpl.manifest.authors.clone(),
+            ignored: tpl.manifest.ignored,
+            depends_on: tpl.manifest.depends_on.clone(),
+            targets: tpl
+                .target_paths()
+                .map(|target| ListedTarget {
+                    target: target.to_string(),
+                    path: paths::expand_target(target),
+                })
+                .collect(),
+            provenance: db.provenance(&tpl.manifest.name).cloned(),
+        })
+        .collect();
+    Listing { templates }
+}
+
+fn list_store(listing: &Listing) {
     println!("{}", "\nStored Templates:\n".bold().underline());
 
-    let templates = db.list();
-    if templates.is_empty() {
+    if listing.templates.is_empty() {
         info("STORE", "No templates installed");
         return;
     }
 
+    // Loose templates first, then one group per package
+    let mut groups: BTreeMap<Option<&PackageRef>, Vec<&ListedTemplate>> = BTreeMap::new();
+    for tpl in &listing.templates {
+        let package = tpl.provenance.as_ref().and_then(|p| p.package.as_ref());
+        groups.entry(package).or_default().push(tpl);
+    }
+
+    for (package, templates) in groups {
+        if let Some(pkg) = package {
+            println!(
+                "{} {}\n",
+                format!("Package {}", pkg.name).magenta().bold(),
+                format!("v{}", pkg.version).green()
+            );
+        }
+        list_templates(&templates);
+    }
+}
+
+fn list_templates(templates: &[&ListedTemplate]) {
     for tpl in templates {
         println!(
             "  {} {}\n    {}\n    {}",
-            tpl.manifest.name.blue().bold(),
-            format!("v{}", tpl.manifest.version).green(),
-            tpl.manifest.description.italic(),
-            format!("by {}", tpl.manifest.authors.join(", ")).dimmed()
+            tpl.name.blue().bold(),
+            format!("v{}", tpl.version).green(),
+            tpl.description.italic(),
+            format!("by {}", tpl.authors.join(", ")).dimmed()
         );
-        if tpl.manifest.ignored {
+        if let Some(prov) = &tpl.provenance {
+            let source = match &prov.entry {
+                Some(entry) => format!("{} ({})", prov.source.display(), entry),
+                None => prov.source.display().to_string(),
+            };
+            println!("    {}", format!("from {}", source).dimmed());
+        }
+        if tpl.ignored {
             println!("    {}", "[DISABLED]".red().bold());
         }
         println!();
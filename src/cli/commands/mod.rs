@@ -1,77 +1,128 @@
 pub mod add;
 pub mod apply;
+pub mod check;
 pub mod compile;
+pub mod daemon;
+pub mod export;
+pub mod keygen;
+pub mod lint;
 pub mod list;
 pub mod pack;
+pub mod pkg;
+pub mod render;
+pub mod repo;
+pub mod show;
+pub mod status;
+pub mod store;
+pub mod sync;
+pub mod theme;
+pub mod trust;
+pub mod update;
+#[cfg(target_os = "linux")]
+pub mod watch;
 
-use super::args::Commands;
+use super::args::{Commands, ConfigCommands, GlobalArgs};
+use super::cli_config::CliConfig;
+use super::exit::{self, Failure};
 use super::logging::{info, warn};
 use crate::config::{self, Config};
 use crate::db::Store;
-use crate::processor;
+use crate::logger;
+use crate::packager::PackOptions;
+use crate::paths::PathPolicy;
+use crate::processor::{self, ApplyOptions};
+use crate::resolver;
+use crate::template::Template;
 use anyhow::{Context, Result};
+use std::path::PathBuf;
 
-pub fn process_command(cmd: Commands) -> Result<()> {
-    let data_dir = config::data_dir();
-    let db_path = data_dir.join("store.db");
-    let mut db = Store::load(&db_path)?;
-    let config = Config::load().context("Failed to load hyprink config")?;
+pub fn process_command(cmd: Commands, global: &GlobalArgs) -> Result<()> {
+    let format = global.output_format();
+    // Keep stdout to the JSON document; warnings and errors still get through
+    logger::set_quiet(format.is_json());
 
+    let db_path = db_path();
     match cmd {
-        Commands::Add { path } => {
-            let installed = add::add_template(&path, &mut db, &config)?;
-            db.save()?;
+        Commands::Store { command } => return store::execute(command, &db_path),
+        Commands::Config {
+            command: ConfigCommands::Check,
+        } => return check::config(format),
+        Commands::Daemon { command } => return daemon::execute(command, &db_path, format),
+        Commands::Keygen { name, force } => return keygen::execute(name, force),
+        Commands::Pkg { command } => return pkg::execute(command, global.root.clone(), format),
+        #[cfg(target_os = "linux")]
+        Commands::Watch { debounce } => {
+            return watch::execute(&db_path, debounce, global.root.clone(), format);
+        }
+        #[cfg(not(target_os = "linux"))]
+        Commands::Watch { .. } => {
+            anyhow::bail!("`hyprink watch` uses inotify and is only available on Linux");
+        }
+        _ => {}
+    }
+    let mut db = Store::load(&db_path)?;
+    let config = load_config()?;
+    let policy = PathPolicy::from_config(&config.paths).with_staging(global.root.clone());
 
-            for tpl in installed {
-                if tpl.manifest.ignored {
-                    info(
-                        "APPLY",
-                        &format!(
-                            "ignoring <secondary>{}</secondary> (disabled)",
-                            tpl.manifest.name
-                        ),
-                    );
-                    continue;
-                }
-                info("APPLY", &format!("applying {}", tpl.manifest.name));
-                let _ = processor::apply(&tpl, &config, false)?;
+    match cmd {
+        Commands::Add { path, trust } => {
+            let installed = add::add_template(&path, &mut db, &config, trust)?;
+            apply_added(&installed, &mut db, &config, policy)?;
+        }
+        Commands::Install { spec, trust } => {
+            let installed = repo::install(&spec, &mut db, &config, trust)?;
+            apply_added(&installed, &mut db, &config, policy)?;
+        }
+        Commands::Upgrade { name, trust } => {
+            let installed = repo::upgrade(name.as_deref(), &mut db, &config, trust)?;
+            apply_added(&installed, &mut db, &config, policy)?;
+        }
+        Commands::Repo { command } => {
+            repo::execute(command, &mut db)?;
+        }
+        Commands::Search { term } => {
+            repo::search(&term, &db)?;
+        }
+        Commands::Theme { command } => {
+            let apply_after = command.apply();
+            if theme::execute(command)? && apply_after {
+                // The config loaded above still has the previous theme
+                let config = load_config()?;
+                let opts = ApplyOptions {
+                    force: false,
+                    policy,
+                    ..Default::default()
+                };
+                apply::execute(&mut db, &config, &opts, format, None)?;
             }
         }
-        Commands::Pack { input, output } => {
-            pack::execute(input, output, &config)?;
+        Commands::Pack {
+            input,
+            output,
+            sign,
+            compression,
+            include,
+            exclude,
+        } => {
+            let opts = PackOptions {
+                compression,
+                include,
+                exclude,
+            };
+            pack::execute(input, output, sign.as_deref(), &opts, &config)?;
         }
         Commands::Apply {
             toggle_force,
             force,
         } => {
-            use super::cli_config::CliConfig;
-
-            let mut current_force = force;
-
-            if toggle_force {
-                let mut cli_conf = CliConfig::load().unwrap_or_default();
-                cli_conf.force_apply = !cli_conf.force_apply;
-
-                if cli_conf.force_apply {
-                    info("APPLY", "FORCE MODE ENABLED (persistent)");
-                } else {
-                    info("APPLY", "FORCE MODE DISABLED (persistent)");
-                }
-
-                if let Err(e) = cli_conf.save() {
-                    warn("APPLY", &format!("Failed to save CLI config: {}", e));
-                }
-
-                if cli_conf.force_apply {
-                    current_force = true;
-                }
-            } else if CliConfig::load().unwrap_or_default().force_apply {
-                current_force = true;
-            }
+            let current_force = CliConfig::resolve_force(toggle_force, force);
 
             let final_config = if current_force {
                 match Config::load_no_cache() {
-                    Ok(c) => c,
+                    Ok(mut c) => {
+                        theme::apply_selected(&mut c);
+                        c
+                    }
                     Err(e) => {
                         warn(
                             "APPLY",
@@ -87,15 +138,137 @@ pub fn process_command(cmd: Commands) -> Result<()> {
             if current_force {
                 warn("APPLY", "APPLYING WITH FORCE (Cache bypassed)");
             }
+            if let Some(staging) = policy.staging() {
+                info(
+                    "APPLY",
+                    &format!("staging output into {}", staging.display()),
+                );
+            }
 
-            apply::execute(&db, &final_config, current_force)?;
+            let opts = ApplyOptions {
+                force: current_force,
+                policy,
+                ..Default::default()
+            };
+            apply::execute(&mut db, &final_config, &opts, format, None)?;
         }
         Commands::List { command } => {
-            list::execute(command, &mut db, &config)?;
+            list::execute(command, &mut db, &config, format)?;
         }
         Commands::Compile => {
             compile::execute(&config)?;
         }
+        Commands::Sync { dir, forget, apply } => {
+            let summary = sync::execute(dir, forget, &mut db, config.packages.signatures)?;
+            if apply && summary.changed() {
+                let opts = ApplyOptions {
+                    force: false,
+                    policy,
+                    ..Default::default()
+                };
+                apply::execute(&mut db, &config, &opts, format, None)?;
+            }
+        }
+        Commands::Store { .. }
+        | Commands::Config { .. }
+        | Commands::Watch { .. }
+        | Commands::Daemon { .. }
+        | Commands::Keygen { .. }
+        | Commands::Pkg { .. } => {
+            unreachable!("handled before the store is loaded")
+        }
+        Commands::Update {
+            name,
+            allow_downgrade,
+        } => {
+            update::execute(name, allow_downgrade, &mut db, config.packages.signatures)?;
+        }
+        Commands::Show { name } => {
+            show::execute(&name, &db, format)?;
+        }
+        Commands::Render {
+            name,
+            target,
+            theme,
+        } => {
+            render::execute(
+                &name,
+                target.as_deref(),
+                theme.as_deref(),
+                &db,
+                config,
+                format,
+            )?;
+        }
+        Commands::Status { name, diff } => {
+            status::execute(name.as_deref(), diff, &db, &config, format)?;
+        }
+        Commands::Lint { template } => {
+            lint::execute(template, &db, &config, &policy, format)?;
+        }
+        Commands::Trust { names, yes } => {
+            trust::execute(names, yes, &mut db)?;
+        }
+    }
+    Ok(())
+}
+
+/// `hyprink.conf` with the selected theme laid over it.
+/// Where the store database lives.
+pub fn db_path() -> PathBuf {
+    config::data_dir().join("store.db")
+}
+
+pub fn load_config() -> Result<Config> {
+    let mut config = Config::load().context(Failure::new(
+        exit::CONFIG_ERROR,
+        "Failed to load hyprink config",
+    ))?;
+    theme::apply_selected(&mut config);
+    Ok(config)
+}
+
+/// Save freshly added templates and apply them in dependency order.
+fn apply_added(
+    installed: &[Template],
+    db: &mut Store,
+    config: &Config,
+    policy: PathPolicy,
+) -> Result<()> {
+    db.save()?;
+
+    let opts = ApplyOptions {
+        force: false,
+        policy,
+        last_written: db.written_checksums(),
+        approved_hooks: db.approved_hooks().clone(),
+    };
+    let installed: Vec<_> = installed.iter().collect();
+    for tpl in resolver::apply_order(&installed)? {
+        if tpl.manifest.ignored {
+            info(
+                "APPLY",
+                &format!(
+                    "ignoring <secondary>{}</secondary> (disabled)",
+                    tpl.manifest.name
+                ),
+            );
+            continue;
+        }
+        info("APPLY", &format!("applying {}", tpl.manifest.name));
+        let outcome = processor::apply(tpl, config, &opts)?;
+        if !outcome.modified.is_empty() {
+            warn(
+                "APPLY",
+                &format!(
+                    "not applying {}: targets changed on disk (run `hyprink apply --force` to overwrite)",
+                    tpl.manifest.name
+                ),
+            );
+        } else if opts.policy.staging().is_none() {
+            apply::record(db, &tpl.manifest.name, &outcome.written);
+        }
     }
+    db.save()?;
     Ok(())
 }
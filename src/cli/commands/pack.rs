@@ -1,15 +1,104 @@
-use super::super::logging::info;
-use crate::config::Config;
-use crate::packager;
-use anyhow::Result;
-use std::path::PathBuf;
+use super::super::logging::{info, warn};
+use crate::config::{self, Config};
+use crate::packager::{self, PackOptions};
+use crate::signing::{self, KeyPair};
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
 
-pub fn execute(input: PathBuf, output: Option<PathBuf>, _config: &Config) -> Result<()> {
+pub fn execute(
+    input: PathBuf,
+    output: Option<PathBuf>,
+    sign: Option<&str>,
+    opts: &PackOptions,
+    _config: &Config,
+) -> Result<()> {
     let out = output.unwrap_or_else(|| {
         let name = input.file_name().unwrap_or_default().to_string_lossy();
         PathBuf::from(format!("{}.pkg", name))
     });
-    packager::pack(&input, &out)?;
-    info("PACK", &format!("packed templates to {}", out.display()));
+    // Load the key first so a bad name fails before anything is written
+    let keys = sign.map(load_key).transpose()?;
+
+    let manifest = packager::pack_with(&input, &out, opts)?;
+    info(
+        "PACK",
+        &format!(
+            "packed {} v{} ({} files) to {}",
+            manifest.package.name,
+            manifest.package.version,
+            manifest.contents.len(),
+            out.display()
+        ),
+    );
+
+    match keys {
+        Some(keys) => {
+            let sig = keys.sign_file(&out)?;
+            info(
+                "PACK",
+                &format!(
+                    "signed with {} ({}): {}",
+                    keys.name,
+                    keys.public().fingerprint(),
+                    sig.display()
+                ),
+            );
+        }
+        None => {
+            // A signature of the previous build would fail every check
+            let sig = signing::signature_path(&out);
+            if sig.exists() {
+                fs::remove_file(&sig)
+                    .with_context(|| format!("Failed to remove {}", sig.display()))?;
+                warn(
+                    "PACK",
+                    &format!("removed {}, which signed the previous build", sig.display()),
+                );
+            }
+        }
+    }
     Ok(())
 }
+
+/// A `.key` file path, or the name of a key in the keys directory.
+fn load_key(key: &str) -> Result<KeyPair> {
+    let path = Path::new(key);
+    if path.is_file() {
+        return KeyPair::load(path);
+    }
+    KeyPair::load(&config::keys_dir().join(format!("{}.{}", key, signing::SECRET_EXT)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_unsigned_pack_removes_stale_signature() {
+        let dir = tempdir().unwrap();
+        let src = dir.path().join("theme");
+        fs::create_dir_all(&src).unwrap();
+        fs::write(
+            src.join("bar.tpl"),
+            "[manifest]\nname = \"bar\"\nversion = \"1.0.0\"\nauthors = []\ndescription = \"\"\n",
+        )
+        .unwrap();
+        let out = dir.path().join("theme.pkg");
+        let sig = signing::signature_path(&out);
+        fs::write(&sig, "stale").unwrap();
+
+        let config = config::test_config();
+        execute(
+            src,
+            Some(out.clone()),
+            None,
+            &PackOptions::default(),
+            &config,
+        )
+        .unwrap();
+        assert!(out.is_file());
+        assert!(!sig.exists());
+    }
+}
@@ -0,0 +1,420 @@
+use super::super::args::PkgCommands;
+use super::super::logging::info;
+use super::super::output::{self, OutputFormat};
+use super::lint::{LintedTemplate, print_issue};
+use crate::config::{self, Config, SignaturePolicy};
+use crate::digest;
+use crate::lint::{self, Issue, Severity};
+use crate::packager::{self, PackageInfo};
+use crate::paths::{self, PathPolicy};
+use crate::signing::{self, Verification};
+use crate::template::Template;
+use anyhow::{Context, Result, bail};
+use colored::*;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Serialize)]
+struct InspectedFile {
+    path: String,
+    size: usize,
+    sha256: String,
+    /// Template defined by the file, `None` for assets
+    template: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct InspectedTarget {
+    /// As written in the template
+    target: String,
+    path: PathBuf,
+}
+
+#[derive(Debug, Serialize)]
+struct InspectedTemplate {
+    entry: String,
+    name: String,
+    version: String,
+    description: String,
+    targets: Vec<InspectedTarget>,
+    hooks: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct Inspected {
+    /// `None` for packages built before `package.toml` existed
+    package: Option<PackageInfo>,
+    signature: Verification,
+    files: Vec<InspectedFile>,
+    templates: Vec<InspectedTemplate>,
+    /// Themes `add` would register, by name
+    themes: Vec<String>,
+    /// Entries that look like templates but do not parse
+    unreadable: Vec<String>,
+}
+
+#[derive(Debug, Default, Serialize)]
+struct VerifyReport {
+    /// No error-level issue in the package or any template.
+    success: bool,
+    errors: usize,
+    warnings: usize,
+    package: Option<PackageInfo>,
+    /// Signature, manifest and checksum findings
+    issues: Vec<Issue>,
+    templates: Vec<LintedTemplate>,
+}
+
+pub fn execute(command: PkgCommands, root: Option<PathBuf>, format: OutputFormat) -> Result<()> {
+    match command {
+        PkgCommands::Inspect { package } => inspect(&package, format),
+        PkgCommands::Verify { package } => verify(&package, root, format),
+    }
+}
+
+fn inspect(package: &Path, format: OutputFormat) -> Result<()> {
+    let inspected = inspect_package(package)?;
+    if format.is_json() {
+        return output::print_json("pkg inspect", &inspected);
+    }
+    print_inspected(package, &inspected);
+    Ok(())
+}
+
+/// Everything a package would install and run, without installing it.
+fn inspect_package(package: &Path) -> Result<Inspected> {
+    let manifest = packager::read_manifest(package)?;
+    let trusted = signing::load_trusted(&config::trusted_keys_dir())?;
+    let mut inspected = Inspected {
+        package: manifest.as_ref().map(|m| m.package.clone()),
+        signature: signing::verify_file(package, &trusted)?,
+        files: Vec::new(),
+        templates: Vec::new(),
+        themes: Vec::new(),
+        unreadable: Vec::new(),
+    };
+
+    for (path, content) in packager::read_files(package)? {
+        let mut defines = None;
+        if let Some(theme) = packager::theme_name(&path) {
+            inspected.themes.push(theme.to_string());
+        }
+        if path.ends_with(".tpl") {
+            match parse(&path, &content) {
+                Ok(tpl) => {
+                    defines = Some(tpl.manifest.name.clone());
+                    inspected.templates.push(InspectedTemplate {
+                        targets: tpl
+                            .target_paths()
+                            .map(|t| InspectedTarget {
+                                target: t.to_string(),
+                                path: paths::expand_target(t),
+                            })
+                            .collect(),
+                        hooks: tpl.hooks.reload.iter().cloned().collect(),
+                        entry: path.clone(),
+                        name: tpl.manifest.name,
+                        version: tpl.manifest.version,
+                        description: tpl.manifest.description,
+                    });
+                }
+                Err(e) => inspected.unreadable.push(format!("{:#}", e)),
+            }
+        }
+        inspected.files.push(InspectedFile {
+            size: content.len(),
+            sha256: digest::sha256_hex(&content),
+            template: defines,
+            path,
+        });
+    }
+    Ok(inspected)
+}
+
+/// Fails if the package has any error-level issue.
+fn verify(package: &Path, root: Option<PathBuf>, format: OutputFormat) -> Result<()> {
+    let config = super::load_config()?;
+    let policy = PathPolicy::from_config(&config.paths).with_staging(root);
+    let report = verify_package(package, &config, &policy)?;
+
+    if format.is_json() {
+        output::print_json("pkg verify", &report)?;
+    } else {
+        print_report(package, &report);
+    }
+
+    if !report.success {
+        bail!(
+            "{} failed verification with {} error(s)",
+            package.display(),
+            report.errors
+        );
+    }
+    Ok(())
+}
+
+/// Check signature, manifest and checksums, then lint every template against
+/// `config`. Problems with the package end up in the report, not in `Err`.
+fn verify_package(package: &Path, config: &Config, policy: &PathPolicy) -> Result<VerifyReport> {
+    let mut report = VerifyReport::default();
+
+    if let Some(issue) = check_signature(package, config.packages.signatures)? {
+        report.issues.push(issue);
+    }
+    let readable = match packager::read_package(package) {
+        Ok(pkg) => {
+            match pkg.manifest {
+                Some(manifest) => report.package = Some(manifest.package),
+                None => report.issues.push(Issue {
+                    severity: Severity::Warning,
+                    location: None,
+                    message: format!(
+                        "no {}; contents cannot be checked against checksums",
+                        packager::MANIFEST
+                    ),
+                }),
+            }
+            true
+        }
+        Err(e) => {
+            report.issues.push(Issue {
+                severity: Severity::Error,
+                location: None,
+                message: format!("{:#}", e),
+            });
+            false
+        }
+    };
+
+    // Lint what can still be read; the failure above is already reported
+    let entries = match packager::read_entries(package) {
+        Ok(entries) => entries,
+        Err(_) if !readable => Vec::new(),
+        Err(e) => return Err(e),
+    };
+    for (path, content) in entries {
+        let linted = match parse(&path, content.as_bytes()) {
+            Ok(tpl) => LintedTemplate {
+                issues: lint::lint_template(&tpl, config, policy),
+                name: tpl.manifest.name,
+            },
+            Err(e) => LintedTemplate {
+                name: path,
+                issues: vec![Issue {
+                    severity: Severity::Error,
+                    location: None,
+                    message: format!("{:#}", e),
+                }],
+            },
+        };
+        report.templates.push(linted);
+    }
+
+    let all_issues = report
+        .issues
+        .iter()
+        .chain(report.templates.iter().flat_map(|t| &t.issues));
+    for issue in all_issues {
+        match issue.severity {
+            Severity::Error => report.errors += 1,
+            Severity::Warning => report.warnings += 1,
+        }
+    }
+    report.success = report.errors == 0;
+    Ok(report)
+}
+
+/// The signature finding `policy` asks for, if any. Unsigned or untrusted
+/// packages are errors only when signatures are required.
+fn check_signature(package: &Path, policy: SignaturePolicy) -> Result<Option<Issue>> {
+    if policy == SignaturePolicy::Off {
+        return Ok(None);
+    }
+    let trusted = signing::load_trusted(&config::trusted_keys_dir())?;
+    let severity = if policy == SignaturePolicy::Require {
+        Severity::Error
+    } else {
+        Severity::Warning
+    };
+    let issue = |severity, message| {
+        Some(Issue {
+            severity,
+            location: Some("signature".to_string()),
+            message,
+        })
+    };
+    Ok(match signing::verify_file(package, &trusted)? {
+        Verification::Trusted { .. } => None,
+        Verification::Invalid { reason } => issue(Severity::Error, reason),
+        Verification::Unsigned => issue(severity, "package is not signed".to_string()),
+        Verification::UntrustedKey { key, fingerprint } => issue(
+            severity,
+            format!("signed by {} ({}), which is not trusted", key, fingerprint),
+        ),
+    })
+}
+
+fn parse(path: &str, content: &[u8]) -> Result<Template> {
+    let text = std::str::from_utf8(content).with_context(|| format!("{} is not UTF-8", path))?;
+    toml::from_str(text).with_context(|| format!("Failed to parse template: {}", path))
+}
+
+fn describe_signature(signature: &Verification) -> ColoredString {
+    match signature {
+        Verification::Trusted { key, fingerprint } => {
+            format!("signed by {} ({})", key, fingerprint).green()
+        }
+        Verification::Unsigned => "not signed".yellow(),
+        Verification::UntrustedKey { key, fingerprint } => {
+            format!("signed by {} ({}), not trusted", key, fingerprint).yellow()
+        }
+        Verification::Invalid { reason } => format!("invalid signature: {}", reason).red(),
+    }
+}
+
+fn print_package(package: &Path, info: Option<&PackageInfo>) {
+    match info {
+        Some(info) => {
+            println!(
+                "\n  {} {}",
+                info.name.blue().bold(),
+                format!("v{}", info.version).green()
+            );
+            if !info.description.is_empty() {
+                println!("    {}", info.description.italic());
+            }
+            if !info.authors.is_empty() {
+                println!("    {}", format!("by {}", info.authors.join(", ")).dimmed());
+            }
+            if let Some(license) = &info.license {
+                println!("    license: {}", license);
+            }
+            if let Some(repo) = &info.repository {
+                println!("    repository: {}", repo);
+            }
+            if let Some(min) = &info.min_hyprink {
+                println!("    requires hyprink {}", min);
+            }
+        }
+        None => println!(
+            "\n  {} {}",
+            package.display().to_string().blue().bold(),
+            format!("(no {})", packager::MANIFEST).dimmed()
+        ),
+    }
+}
+
+fn print_inspected(package: &Path, inspected: &Inspected) {
+    print_package(package, inspected.package.as_ref());
+    println!("    {}", describe_signature(&inspected.signature));
+
+    println!("\n  {}", "Templates".bold());
+    for tpl in &inspected.templates {
+        println!(
+            "    {} {} {}",
+            tpl.name.blue().bold(),
+            format!("v{}", tpl.version).green(),
+            format!("({})", tpl.entry).dimmed()
+        );
+        for t in &tpl.targets {
+            println!("      {} {} {}", t.target, "->".dimmed(), t.path.display());
+        }
+        for hook in &tpl.hooks {
+            println!("      {} {}", "runs".yellow(), hook);
+        }
+    }
+    if !inspected.themes.is_empty() {
+        println!("\n  {}", "Themes".bold());
+        for theme in &inspected.themes {
+            println!("    {}", theme);
+        }
+    }
+    for error in &inspected.unreadable {
+        println!("    {} {}", "error".red().bold(), error);
+    }
+
+    println!("\n  {}", "Files".bold());
+    for file in &inspected.files {
+        println!(
+            "    {} {}",
+            file.path,
+            format!("{} bytes, sha256 {}", file.size, &file.sha256[..16]).dimmed()
+        );
+    }
+    println!();
+}
+
+fn print_report(package: &Path, report: &VerifyReport) {
+    print_package(package, report.package.as_ref());
+    for issue in &report.issues {
+        print_issue(issue);
+    }
+    println!();
+
+    for tpl in &report.templates {
+        if tpl.issues.is_empty() {
+            println!("  {} {}", tpl.name.blue().bold(), "ok".green());
+            continue;
+        }
+        println!("  {}", tpl.name.blue().bold());
+        for issue in &tpl.issues {
+            print_issue(issue);
+        }
+    }
+
+    info(
+        "PKG",
+        &format!(
+            "{} templates, {} errors, {} warnings",
+            report.templates.len(),
+            report.errors,
+            report.warnings
+        ),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_verify_reports_tampered_packages() {
+        let dir = tempdir().unwrap();
+        let pkg = dir.path().join("demo.pkg");
+        let tpl =
+            "[manifest]\nname = \"demo\"\nversion = \"1.0.0\"\nauthors = []\ndescription = \"\"\n";
+        let entries = vec![("demo.tpl".to_string(), tpl.as_bytes().to_vec())];
+        let manifest =
+            packager::manifest_for(PackageInfo::new("demo-pkg", "1.0.0"), &entries).unwrap();
+        let mut config = config::test_config();
+        config.packages.signatures = SignaturePolicy::Off;
+        let policy = PathPolicy::from_config(&config.paths);
+
+        packager::write_package(&pkg, &manifest, &entries).unwrap();
+        let report = verify_package(&pkg, &config, &policy).unwrap();
+        assert!(report.success, "{:?}", report.issues);
+
+        // Contents no longer match package.toml
+        let tampered = vec![(
+            "demo.tpl".to_string(),
+            tpl.replace("1.0.0", "6.6.6").into_bytes(),
+        )];
+        packager::write_package(&pkg, &manifest, &tampered).unwrap();
+        let report = verify_package(&pkg, &config, &policy).unwrap();
+        assert!(!report.success);
+        assert!(report.issues[0].message.contains("checksum"));
+        assert_eq!(report.templates[0].name, "demo");
+        let inspected = inspect_package(&pkg).unwrap();
+        assert_eq!(inspected.templates[0].version, "6.6.6");
+
+        // Unreadable entries fail the report instead of aborting it
+        let garbled = vec![("demo.tpl".to_string(), vec![0xff, 0xfe])];
+        packager::write_package(&pkg, &manifest, &garbled).unwrap();
+        let report = verify_package(&pkg, &config, &policy).unwrap();
+        assert_eq!(report.errors, 1);
+        assert!(report.templates.is_empty());
+        let inspected = inspect_package(&pkg).unwrap();
+        assert_eq!(inspected.unreadable.len(), 1);
+    }
+}
@@ -0,0 +1,99 @@
+use super::super::output::{self, OutputFormat};
+use crate::config::Config;
+use crate::db::Store;
+use crate::paths;
+use crate::processor;
+use crate::template::{Target, Template};
+use anyhow::{Context, Result, anyhow};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+#[derive(Serialize)]
+struct Rendered<'a> {
+    index: usize,
+    target: &'a str,
+    path: PathBuf,
+    content: String,
+}
+
+#[derive(Serialize)]
+struct RenderOutput<'a> {
+    name: &'a str,
+    outputs: Vec<Rendered<'a>>,
+}
+
+pub fn execute(
+    name: &str,
+    target: Option<&str>,
+    theme: Option<&Path>,
+    db: &Store,
+    mut config: Config,
+    format: OutputFormat,
+) -> Result<()> {
+    let tpl = db
+        .get(name)
+        .ok_or_else(|| anyhow!("template '{}' not found", name))?;
+
+    if let Some(theme) = theme {
+        config
+            .overlay_theme(theme)
+            .with_context(|| format!("Failed to load theme from {}", theme.display()))?;
+    }
+
+    let selected = select(tpl, target)?;
+    let (mut tera, ctx) = processor::render_context(&config);
+
+    let mut outputs = Vec::new();
+    for (index, out) in selected {
+        let content = tera
+            .render_str(&out.content, &ctx)
+            .with_context(|| format!("Failed to render {}", out.target))?;
+        outputs.push(Rendered {
+            index,
+            target: &out.target,
+            path: paths::expand_target(&out.target),
+            content,
+        });
+    }
+
+    if format.is_json() {
+        return output::print_json("render", &RenderOutput { name, outputs });
+    }
+
+    // Headers only when there is more than one output, so a single one can be piped as-is
+    let headers = outputs.len() > 1;
+    for (i, out) in outputs.iter().enumerate() {
+        if headers {
+            if i > 0 {
+                println!();
+            }
+            println!("==> [{}] {} <==", out.index, out.target);
+        }
+        print!("{}", out.content);
+        if headers && !out.content.ends_with('\n') {
+            println!();
+        }
+    }
+    Ok(())
+}
+
+/// All outputs, or the one picked by index (as numbered by `show`) or target path.
+fn select<'a>(tpl: &'a Template, target: Option<&str>) -> Result<Vec<(usize, &'a Target)>> {
+    let all = tpl.outputs().enumerate();
+    let Some(wanted) = target else {
+        return Ok(all.collect());
+    };
+
+    let found = match wanted.parse::<usize>() {
+        Ok(n) => all.into_iter().nth(n),
+        Err(_) => all.into_iter().find(|(_, t)| t.target == wanted),
+    };
+    found.map(|hit| vec![hit]).ok_or_else(|| {
+        anyhow!(
+            "{} has no target '{}' (see `hyprink show {}`)",
+            tpl.manifest.name,
+            wanted,
+            tpl.manifest.name
+        )
+    })
+}
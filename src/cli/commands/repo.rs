@@ -0,0 +1,214 @@
+use super::super::args::RepoCommands;
+use super::super::logging::{info, warn};
+use super::add;
+use crate::config::Config;
+use crate::db::Store;
+use crate::packager;
+use crate::repository::{self, Index, IndexEntry};
+use crate::template::{self, Template};
+use anyhow::{Result, bail};
+use colored::*;
+use semver::VersionReq;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub fn execute(command: RepoCommands, db: &mut Store) -> Result<()> {
+    match command {
+        RepoCommands::Add { location } => {
+            let repo = repository::location(&location)?;
+            let index = Index::load(&repo)?;
+            if db.add_repository(&repo) {
+                db.save()?;
+                info(
+                    "REPO",
+                    &format!(
+                        "added {} ({} packages)",
+                        repo.display(),
+                        index.packages.len()
+                    ),
+                );
+            } else {
+                info("REPO", &format!("{} is already added", repo.display()));
+            }
+        }
+        RepoCommands::Remove { location } => {
+            // The repository may be gone already, so match on the path as given too
+            let path = location.strip_prefix("file://").unwrap_or(&location);
+            let repo = fs::canonicalize(path).unwrap_or_else(|_| PathBuf::from(path));
+            if db.remove_repository(&repo) {
+                db.save()?;
+                info("REPO", &format!("removed {}", repo.display()));
+            } else {
+                warn("REPO", &format!("{} is not a repository", repo.display()));
+            }
+        }
+        RepoCommands::List => {
+            if db.repositories().is_empty() {
+                info(
+                    "REPO",
+                    "no repositories (use `hyprink repo add <dir>` to add one)",
+                );
+            }
+            for repo in db.repositories() {
+                let state = match Index::load(repo) {
+                    Ok(index) => format!("{} packages", index.packages.len()).dimmed(),
+                    Err(e) => format!("unreadable: {:#}", e).red(),
+                };
+                println!("  {} {}", repo.display().to_string().bold(), state);
+            }
+        }
+        RepoCommands::Index { dir } => {
+            let (index, skipped) = Index::build(&dir)?;
+            for path in skipped {
+                warn(
+                    "REPO",
+                    &format!(
+                        "skipping {}: no {} (repack it with `hyprink pack`)",
+                        path.display(),
+                        packager::MANIFEST
+                    ),
+                );
+            }
+            index.save(&dir)?;
+            info(
+                "REPO",
+                &format!(
+                    "indexed {} packages in {}",
+                    index.packages.len(),
+                    dir.join(repository::INDEX).display()
+                ),
+            );
+        }
+    }
+    Ok(())
+}
+
+pub fn search(term: &str, db: &Store) -> Result<()> {
+    let indexes = load_indexes(db)?;
+    let mut found = 0;
+    for (repo, index) in &indexes {
+        for entry in index.search(term) {
+            found += 1;
+            println!(
+                "  {} {} {}",
+                entry.name.blue().bold(),
+                format!("v{}", entry.version).green(),
+                format!("({})", repo.display()).dimmed()
+            );
+            if !entry.description.is_empty() {
+                println!("    {}", entry.description.italic());
+            }
+        }
+    }
+    if found == 0 {
+        info("REPO", &format!("no packages match '{}'", term));
+    }
+    Ok(())
+}
+
+/// Install the newest package matching `spec` (`name[@version]`).
+pub fn install(spec: &str, db: &mut Store, config: &Config, trust: bool) -> Result<Vec<Template>> {
+    let (name, req) = repository::parse_spec(spec)?;
+    let indexes = load_indexes(db)?;
+    let Some((repo, entry)) = repository::resolve(&indexes, name, &req) else {
+        bail!(
+            "no package {} matching '{}' in {} repositories",
+            name,
+            req,
+            indexes.len()
+        );
+    };
+    install_entry(repo, entry, db, config, trust)
+}
+
+/// Install the newest version of every package (or just `name`) that has
+/// one in the repositories.
+pub fn upgrade(
+    name: Option<&str>,
+    db: &mut Store,
+    config: &Config,
+    trust: bool,
+) -> Result<Vec<Template>> {
+    let mut installed = repository::installed_packages(db);
+    if let Some(name) = name {
+        installed.retain(|pkg, _| pkg == name);
+        if installed.is_empty() {
+            bail!("no templates were installed from a package named {}", name);
+        }
+    }
+
+    let indexes = load_indexes(db)?;
+    let mut upgraded = Vec::new();
+    for (pkg, current) in installed {
+        match repository::resolve(&indexes, &pkg, &VersionReq::STAR) {
+            Some((repo, entry))
+                if template::parse_version(&entry.version) > Some(current.clone()) =>
+            {
+                info(
+                    "REPO",
+                    &format!("upgrading {} v{} -> v{}", pkg, current, entry.version),
+                );
+                upgraded.extend(install_entry(repo, entry, db, config, trust)?);
+            }
+            Some(_) => info("REPO", &format!("{} v{} is up to date", pkg, current)),
+            None => warn("REPO", &format!("{} is not in any repository", pkg)),
+        }
+    }
+    Ok(upgraded)
+}
+
+/// Every remembered repository with its index; unreadable ones are skipped
+/// with a warning.
+fn load_indexes(db: &Store) -> Result<Vec<(PathBuf, Index)>> {
+    if db.repositories().is_empty() {
+        bail!("no repositories (use `hyprink repo add <dir>` to add one)");
+    }
+    Ok(db
+        .repositories()
+        .iter()
+        .filter_map(|repo| match Index::load(repo) {
+            Ok(index) => Some((repo.clone(), index)),
+            Err(e) => {
+                warn("REPO", &format!("skipping {}: {:#}", repo.display(), e));
+                None
+            }
+        })
+        .collect())
+}
+
+/// Remove templates an earlier version of the package had that `entry` does
+/// not, then install `entry`.
+fn install_entry(
+    repo: &Path,
+    entry: &IndexEntry,
+    db: &mut Store,
+    config: &Config,
+    trust: bool,
+) -> Result<Vec<Template>> {
+    let path = repository::fetch(repo, entry)?;
+    info(
+        "REPO",
+        &format!(
+            "installing {} v{} from {}",
+            entry.name,
+            entry.version,
+            repo.display()
+        ),
+    );
+    let shipped = packager::read_package(&path)?
+        .templates
+        .into_iter()
+        .map(|(_, tpl)| tpl.manifest.name)
+        .collect();
+    for name in repository::dropped_templates(db, &entry.name, &shipped) {
+        db.remove(&name);
+        info(
+            "REPO",
+            &format!(
+                "removed {} (no longer in {} v{})",
+                name, entry.name, entry.version
+            ),
+        );
+    }
+    add::add_template(&path, db, config, trust)
+}
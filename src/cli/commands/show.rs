@@ -0,0 +1,152 @@
+use super::super::output::{self, OutputFormat};
+use crate::db::{Provenance, Store};
+use crate::paths;
+use crate::template::Template;
+use anyhow::{Result, anyhow};
+use colored::*;
+use serde::Serialize;
+use std::path::PathBuf;
+
+#[derive(Serialize)]
+struct Shown<'a> {
+    #[serde(flatten)]
+    template: &'a Template,
+    /// Expanded path of every output, indexed like `render --target`.
+    paths: Vec<PathBuf>,
+    missing_dependencies: Vec<&'a str>,
+    provenance: Option<&'a Provenance>,
+}
+
+pub fn execute(name: &str, db: &Store, format: OutputFormat) -> Result<()> {
+    let tpl = db
+        .get(name)
+        .ok_or_else(|| anyhow!("template '{}' not found", name))?;
+
+    let shown = Shown {
+        template: tpl,
+        paths: tpl.target_paths().map(paths::expand_target).collect(),
+        missing_dependencies: tpl
+            .manifest
+            .depends_on
+            .iter()
+            .map(String::as_str)
+            .filter(|dep| !db.contains(dep))
+            .collect(),
+        provenance: db.provenance(name),
+    };
+
+    if format.is_json() {
+        return output::print_json("show", &shown);
+    }
+    print_text(&shown);
+    Ok(())
+}
+
+fn print_text(shown: &Shown) {
+    let tpl = shown.template;
+    let m = &tpl.manifest;
+
+    println!(
+        "\n  {} {}{}",
+        m.name.blue().bold(),
+        format!("v{}", m.version).green(),
+        if m.ignored {
+            format!(" {}", "[DISABLED]".red().bold())
+        } else {
+            String::new()
+        }
+    );
+    if !m.description.is_empty() {
+        println!("    {}", m.description.italic());
+    }
+    if !m.authors.is_empty() {
+        println!("    {}", format!("by {}", m.authors.join(", ")).dimmed());
+    }
+    if let Some(license) = &m.license {
+        println!("    license: {}", license);
+    }
+    if let Some(repo) = &m.repository {
+        println!("    repository: {}", repo);
+    }
+
+    let mut index = 0;
+    for (title, outputs) in [("Targets", &tpl.targets), ("Files", &tpl.files)] {
+        if outputs.is_empty() {
+            continue;
+        }
+        println!("\n  {}", title.bold());
+        for out in outputs {
+            println!(
+                "    [{}] {} {} {}",
+                index,
+                out.target,
+                "->".dimmed(),
+                shown.paths[index].display()
+            );
+            println!(
+                "        {}",
+                format!("{} lines", out.content.lines().count()).dimmed()
+            );
+            index += 1;
+        }
+    }
+
+    if let Some(reload) = &tpl.hooks.reload {
+        println!("\n  {}", "Hooks".bold());
+        println!("    reload: {}", reload);
+    }
+
+    let requirements = [
+        ("depends_on", &m.depends_on),
+        ("after", &m.after),
+        ("overrides", &m.overrides),
+    ];
+    if requirements.iter().any(|(_, names)| !names.is_empty()) {
+        println!("\n  {}", "Requirements".bold());
+        for (label, names) in requirements {
+            if names.is_empty() {
+                continue;
+            }
+            let names: Vec<String> = names
+                .iter()
+                .map(|n| {
+                    if shown.missing_dependencies.contains(&n.as_str()) {
+                        format!("{} (missing)", n).red().to_string()
+                    } else {
+                        n.clone()
+                    }
+                })
+                .collect();
+            println!("    {}: {}", label, names.join(", "));
+        }
+    }
+
+    println!("\n  {}", "Source".bold());
+    match shown.provenance {
+        Some(prov) => {
+            let mut source = prov.source.display().to_string();
+            if let Some(entry) = &prov.entry {
+                source.push_str(&format!(" ({})", entry));
+            }
+            if !prov.source.exists() {
+                source.push_str(&format!(" {}", "(no longer exists)".yellow()));
+            }
+            println!("    {}", source);
+            if let Some(pkg) = &prov.package {
+                println!("    package {} v{}", pkg.name, pkg.version);
+            }
+            println!("    {}", format!("sha256 {}", prov.checksum).dimmed());
+            if let Some(added) = chrono::DateTime::from_timestamp(prov.added_at, 0) {
+                println!(
+                    "    {}",
+                    format!("added {}", added.format("%Y-%m-%d %H:%M UTC")).dimmed()
+                );
+            }
+        }
+        None => println!(
+            "    {}",
+            "unknown (added before provenance was recorded)".dimmed()
+        ),
+    }
+    println!();
+}
@@ -0,0 +1,143 @@
+use super::super::logging::{info, warn};
+use super::super::output::{self, OutputFormat};
+use crate::config::Config;
+use crate::db::Store;
+use crate::paths::PathPolicy;
+use crate::status::{self, TargetState, TargetStatus};
+use anyhow::{Result, anyhow};
+use colored::*;
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+#[derive(Serialize, Deserialize)]
+pub struct TemplateState {
+    name: String,
+    ignored: bool,
+    targets: Vec<TargetStatus>,
+}
+
+/// The `status --format json` document, also served by the daemon.
+#[derive(Serialize, Deserialize)]
+pub struct StatusReport {
+    templates: Vec<TemplateState>,
+}
+
+pub fn execute(
+    name: Option<&str>,
+    diff: bool,
+    db: &Store,
+    config: &Config,
+    format: OutputFormat,
+) -> Result<()> {
+    print(&report(name, db, config)?, diff, format)
+}
+
+/// Print a report made here or by the daemon. `diff` needs the renders,
+/// which only a local report has.
+pub fn print(report: &StatusReport, diff: bool, format: OutputFormat) -> Result<()> {
+    if format.is_json() {
+        return output::print_json("status", &report);
+    }
+
+    if report.templates.is_empty() {
+        info("STATUS", "No templates installed");
+        return Ok(());
+    }
+
+    let mut counts = [0usize; 5];
+    for tpl in &report.templates {
+        let disabled = if tpl.ignored {
+            format!(" {}", "[DISABLED]".red().bold())
+        } else {
+            String::new()
+        };
+        println!("  {}{}", tpl.name.blue().bold(), disabled);
+        for t in &tpl.targets {
+            counts[t.state as usize] += 1;
+            println!("    {} {}", label(t.state), t.target);
+            if let Some(err) = &t.error {
+                println!("      {}", err.dimmed());
+            }
+            if diff && matches!(t.state, TargetState::Modified | TargetState::Stale) {
+                show_diff(t);
+            }
+        }
+    }
+
+    info(
+        "STATUS",
+        &format!(
+            "{} in sync, {} modified, {} missing, {} stale, {} unapplied",
+            counts[TargetState::InSync as usize],
+            counts[TargetState::Modified as usize],
+            counts[TargetState::Missing as usize],
+            counts[TargetState::Stale as usize],
+            counts[TargetState::Unapplied as usize]
+        ),
+    );
+    Ok(())
+}
+
+/// Status of one stored template, or all of them.
+pub fn report(name: Option<&str>, db: &Store, config: &Config) -> Result<StatusReport> {
+    let templates = match name {
+        Some(name) => vec![
+            db.get(name)
+                .ok_or_else(|| anyhow!("template '{}' not found", name))?,
+        ],
+        None => db.list(),
+    };
+
+    // Write records are kept for the real paths, so staging does not apply here
+    let policy = PathPolicy::from_config(&config.paths).without_staging();
+    Ok(StatusReport {
+        templates: templates
+            .into_iter()
+            .map(|tpl| TemplateState {
+                name: tpl.manifest.name.clone(),
+                ignored: tpl.manifest.ignored,
+                targets: status::template_status(tpl, db, config, &policy),
+            })
+            .collect(),
+    })
+}
+
+fn label(state: TargetState) -> ColoredString {
+    let text = format!("{:<10}", state.as_str());
+    match state {
+        TargetState::InSync => text.green(),
+        TargetState::Modified => text.red().bold(),
+        TargetState::Missing => text.red(),
+        TargetState::Stale => text.yellow(),
+        TargetState::Unapplied => text.dimmed(),
+    }
+}
+
+/// `diff -u` from what apply would write now to what is on disk.
+fn show_diff(t: &TargetStatus) {
+    let Some(rendered) = &t.rendered else {
+        return;
+    };
+    let child = Command::new("diff")
+        .arg("-u")
+        .arg("--label")
+        .arg(format!("{} (hyprink)", t.target))
+        .arg("--label")
+        .arg(t.path.display().to_string())
+        .arg("-")
+        .arg(&t.path)
+        .stdin(Stdio::piped())
+        .spawn();
+    let mut child = match child {
+        Ok(child) => child,
+        Err(e) => {
+            warn("STATUS", &format!("cannot run diff: {}", e));
+            return;
+        }
+    };
+    if let Some(mut stdin) = child.stdin.take() {
+        let _ = stdin.write_all(rendered.as_bytes());
+    }
+    let _ = child.wait();
+}
@@ -0,0 +1,111 @@
+use super::super::args::StoreCommands;
+use super::super::logging::{error, info};
+use super::export;
+use crate::db::Store;
+use anyhow::{Result, anyhow};
+use colored::*;
+use std::path::{Path, PathBuf};
+
+/// Verify and restore work on the raw database file, so a store that fails
+/// to load can still be inspected and repaired.
+pub fn execute(command: StoreCommands, db_path: &Path) -> Result<()> {
+    match command {
+        StoreCommands::Verify => verify(db_path),
+        StoreCommands::Restore { snapshot } => restore(db_path, snapshot),
+        StoreCommands::Export { dest, templates } => {
+            export::export(&dest, &templates, &Store::load(db_path)?)
+        }
+        StoreCommands::Import { source, overwrite } => {
+            let signatures = super::load_config()?.packages.signatures;
+            export::import(&source, overwrite, &mut Store::load(db_path)?, signatures)
+        }
+    }
+}
+
+fn verify(db_path: &Path) -> Result<()> {
+    let current_ok = if db_path.exists() {
+        report(db_path)
+    } else {
+        info("STORE", "no store database yet");
+        true
+    };
+
+    let snapshots = Store::snapshots(db_path);
+    if snapshots.is_empty() {
+        info("STORE", "no snapshots");
+    }
+    for snapshot in &snapshots {
+        report(snapshot);
+    }
+
+    if current_ok {
+        Ok(())
+    } else {
+        Err(anyhow!(
+            "Store database is corrupt; run `hyprink store restore` to roll back to a snapshot"
+        ))
+    }
+}
+
+fn report(path: &Path) -> bool {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    match Store::verify_file(path) {
+        Ok(info) => {
+            println!(
+                "  {} {} {}",
+                "ok".green().bold(),
+                name,
+                format!("(schema v{}, {} templates)", info.schema, info.templates).dimmed()
+            );
+            true
+        }
+        Err(e) => {
+            println!(
+                "  {} {} {}",
+                "corrupt".red().bold(),
+                name,
+                format!("({:#})", e).dimmed()
+            );
+            false
+        }
+    }
+}
+
+fn restore(db_path: &Path, snapshot: Option<String>) -> Result<()> {
+    let snapshots = Store::snapshots(db_path);
+    let candidates: Vec<PathBuf> = match &snapshot {
+        Some(name) => snapshots
+            .into_iter()
+            .filter(|p| p.file_name().is_some_and(|n| n.to_string_lossy() == *name))
+            .collect(),
+        None => snapshots,
+    };
+
+    for candidate in &candidates {
+        match Store::restore_snapshot(db_path, candidate) {
+            Ok(restored) => {
+                info(
+                    "STORE",
+                    &format!(
+                        "restored {} templates from {}",
+                        restored.templates,
+                        candidate.display()
+                    ),
+                );
+                return Ok(());
+            }
+            Err(e) if snapshot.is_none() => {
+                error(
+                    "STORE",
+                    &format!("skipping {}: {:#}", candidate.display(), e),
+                );
+            }
+            Err(e) => return Err(e),
+        }
+    }
+
+    match snapshot {
+        Some(name) => Err(anyhow!("Snapshot '{}' not found", name)),
+        None => Err(anyhow!("No valid snapshot to restore")),
+    }
+}
@@ -0,0 +1,242 @@
+use super::super::logging::{error, info, warn};
+use super::{add, trust, update};
+use crate::config::SignaturePolicy;
+use crate::db::{Provenance, Store};
+use crate::digest;
+use crate::template::Template;
+use anyhow::{Context, Result, anyhow};
+use colored::*;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Default)]
+pub struct SyncSummary {
+    pub added: Vec<String>,
+    pub updated: Vec<String>,
+    pub removed: Vec<String>,
+    pub failed: Vec<String>,
+}
+
+impl SyncSummary {
+    pub fn changed(&self) -> bool {
+        !(self.added.is_empty() && self.updated.is_empty() && self.removed.is_empty())
+    }
+}
+
+/// Mirror `dir` (or every remembered directory) into the store.
+pub fn execute(
+    dir: Option<PathBuf>,
+    forget: bool,
+    db: &mut Store,
+    signatures: SignaturePolicy,
+) -> Result<SyncSummary> {
+    let dirs = match dir {
+        Some(dir) => {
+            let dir = fs::canonicalize(&dir)
+                .with_context(|| format!("Directory not found: {:?}", dir))?;
+            if forget {
+                if db.remove_synced_dir(&dir) {
+                    db.save()?;
+                    info("SYNC", &format!("no longer syncing {}", dir.display()));
+                } else {
+                    warn(
+                        "SYNC",
+                        &format!("{} is not a synced directory", dir.display()),
+                    );
+                }
+                return Ok(SyncSummary::default());
+            }
+            if !dir.is_dir() {
+                return Err(anyhow!("Not a directory: {:?}", dir));
+            }
+            if db.add_synced_dir(&dir) {
+                info("SYNC", &format!("now syncing {}", dir.display()));
+            }
+            vec![dir]
+        }
+        None => db.synced_dirs().to_vec(),
+    };
+
+    let mut summary = SyncSummary::default();
+    if dirs.is_empty() {
+        info(
+            "SYNC",
+            "no synced directories (use `hyprink sync <dir>` to add one)",
+        );
+        return Ok(summary);
+    }
+
+    for dir in &dirs {
+        sync_dir(dir, db, &mut summary, signatures)?;
+    }
+    db.save()?;
+
+    print_summary(&summary);
+    Ok(summary)
+}
+
+fn sync_dir(
+    dir: &Path,
+    db: &mut Store,
+    summary: &mut SyncSummary,
+    signatures: SignaturePolicy,
+) -> Result<()> {
+    if !dir.is_dir() {
+        warn(
+            "SYNC",
+            &format!("{} no longer exists, skipping", dir.display()),
+        );
+        return Ok(());
+    }
+
+    let mut sources: Vec<PathBuf> = fs::read_dir(dir)?
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| {
+            p.extension()
+                .is_some_and(|ext| ext == "tpl" || ext == "pkg")
+        })
+        .collect();
+    sources.sort();
+
+    let now = chrono::Utc::now().timestamp();
+    let mut desired: BTreeMap<String, (Template, Provenance)> = BTreeMap::new();
+    let mut parse_failed = false;
+    for path in sources {
+        // Warning on every unsigned package each time a directory is
+        // refreshed would just be noise, so only failures are reported
+        let checked = if path.extension().is_some_and(|ext| ext == "pkg") {
+            add::check_signature_quietly(&path, signatures)
+        } else {
+            Ok(())
+        };
+        let parsed = match checked.and_then(|_| add::read_source(&path)) {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                error("SYNC", &format!("{}: {:#}", path.display(), e));
+                summary.failed.push(path.display().to_string());
+                parse_failed = true;
+                continue;
+            }
+        };
+        let checksum = digest::sha256_file(&path)?;
+        for parsed in parsed {
+            let provenance = parsed.provenance(&path, &checksum, now);
+            let name = parsed.template.manifest.name.clone();
+            if desired
+                .insert(name.clone(), (parsed.template, provenance))
+                .is_some()
+            {
+                warn(
+                    "SYNC",
+                    &format!(
+                        "{} is defined more than once, using {}",
+                        name,
+                        path.display()
+                    ),
+                );
+            }
+        }
+    }
+
+    // A source that failed to parse may still provide stored templates
+    if !parse_failed {
+        for name in db.templates_from(dir) {
+            if !desired.contains_key(&name) {
+                db.remove(&name);
+                summary.removed.push(name);
+            }
+        }
+    }
+
+    for (name, (mut tpl, provenance)) in desired {
+        // Letting each sync take the template over would flip it back and forth
+        let owner = db
+            .provenance(&name)
+            .and_then(|p| p.source.parent())
+            .filter(|owner| *owner != dir && db.synced_dirs().iter().any(|d| d == owner))
+            .map(Path::to_path_buf);
+        if let Some(owner) = owner {
+            warn(
+                "SYNC",
+                &format!(
+                    "skipping {} from {}: it is already synced from {}",
+                    name,
+                    provenance.source.display(),
+                    owner.display()
+                ),
+            );
+            continue;
+        }
+
+        let is_new = match db.get(&name) {
+            Some(stored) => {
+                let unchanged = db.provenance(&name).is_some_and(|p| {
+                    p.source == provenance.source && p.checksum == provenance.checksum
+                });
+                if unchanged {
+                    continue;
+                }
+                tpl.manifest.ignored = stored.manifest.ignored;
+                if update::same_content(stored, &tpl) {
+                    db.set_provenance(&name, provenance);
+                    continue;
+                }
+                false
+            }
+            None => true,
+        };
+
+        if let Some(conflict) = db.conflicts_with(&tpl).first() {
+            error(
+                "SYNC",
+                &format!(
+                    "skipping {}: {} is also written by {}",
+                    name,
+                    conflict.path.display(),
+                    conflict.templates.join(", ")
+                ),
+            );
+            summary.failed.push(name);
+            continue;
+        }
+
+        trust::warn_unapproved(&tpl, db, "SYNC");
+        db.add(tpl)?;
+        db.set_provenance(&name, provenance);
+        if is_new {
+            summary.added.push(name);
+        } else {
+            summary.updated.push(name);
+        }
+    }
+
+    Ok(())
+}
+
+fn print_summary(summary: &SyncSummary) {
+    for name in &summary.added {
+        println!("  {} {}", "+".green().bold(), name);
+    }
+    for name in &summary.updated {
+        println!("  {} {}", "~".yellow().bold(), name);
+    }
+    for name in &summary.removed {
+        println!("  {} {}", "-".red().bold(), name);
+    }
+
+    let msg = format!(
+        "{} added, {} updated, {} removed",
+        summary.added.len(),
+        summary.updated.len(),
+        summary.removed.len()
+    );
+    if summary.failed.is_empty() {
+        info("SYNC", &msg);
+    } else {
+        warn(
+            "SYNC",
+            &format!("{} ({} failed)", msg, summary.failed.len()),
+        );
+    }
+}
@@ -0,0 +1,61 @@
+use super::super::args::ThemeCommands;
+use super::super::logging::{info, warn};
+use crate::config::{self, Config};
+use crate::themes;
+use anyhow::{Result, bail};
+use colored::*;
+
+/// What the caller should do after the command: whether the selection changed.
+pub fn execute(command: ThemeCommands) -> Result<bool> {
+    let dir = config::themes_dir();
+    match command {
+        ThemeCommands::List => {
+            let installed = themes::list(&dir)?;
+            if installed.is_empty() {
+                info("THEME", "no themes installed (they come with packages)");
+                return Ok(false);
+            }
+            let selected = themes::selected(&dir);
+            for theme in installed {
+                let marker = if selected.as_deref() == Some(theme.name.as_str()) {
+                    "*".green().bold()
+                } else {
+                    " ".normal()
+                };
+                let title = theme
+                    .title
+                    .filter(|t| *t != theme.name)
+                    .map(|t| format!(" {}", t.italic()))
+                    .unwrap_or_default();
+                println!("  {} {}{}", marker, theme.name.blue().bold(), title);
+            }
+            Ok(false)
+        }
+        ThemeCommands::Use { name, .. } => {
+            themes::select(&dir, Some(&name))?;
+            info("THEME", &format!("selected theme {}", name));
+            Ok(true)
+        }
+        ThemeCommands::Reset { .. } => {
+            themes::select(&dir, None)?;
+            info("THEME", "using hyprink.conf without a theme");
+            Ok(true)
+        }
+        ThemeCommands::Remove { name } => {
+            let was_selected = themes::selected(&dir).as_deref() == Some(name.as_str());
+            if !themes::remove(&dir, &name)? {
+                bail!("theme '{}' is not installed", name);
+            }
+            info("THEME", &format!("removed theme {}", name));
+            Ok(was_selected)
+        }
+    }
+}
+
+/// Lay the selected theme over a freshly loaded config. A theme that cannot
+/// be loaded is skipped with a warning rather than failing the command.
+pub fn apply_selected(config: &mut Config) {
+    if let Err(e) = themes::overlay_selected(&config::themes_dir(), config) {
+        warn("THEME", &format!("{:#} (using hyprink.conf without it)", e));
+    }
+}
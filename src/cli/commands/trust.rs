@@ -0,0 +1,70 @@
+use super::super::logging::{info, warn};
+use super::add;
+use crate::db::Store;
+use crate::processor;
+use crate::template::Template;
+use anyhow::{Result, anyhow, bail};
+use std::io::{self, IsTerminal};
+
+/// Approve the reload hooks of stored templates, e.g. ones that came in
+/// through `sync`, `update` or `store import`.
+pub fn execute(names: Vec<String>, yes: bool, db: &mut Store) -> Result<()> {
+    let mut approved = 0;
+    for name in names {
+        let tpl = db
+            .get(&name)
+            .ok_or_else(|| anyhow!("template '{}' not found", name))?;
+        let Some(cmd) = tpl.hooks.reload.clone() else {
+            info("TRUST", &format!("{} has no reload hook", name));
+            continue;
+        };
+        let hook = processor::hook_digest(&cmd);
+        if db.approved_hooks().get(&name) == Some(&hook) {
+            info("TRUST", &format!("hook of {} is already approved", name));
+            continue;
+        }
+
+        warn(
+            "TRUST",
+            &format!("{} runs this command after every apply: {}", name, cmd),
+        );
+        if !yes {
+            if !io::stdin().is_terminal() {
+                bail!(
+                    "Not approving {} without a terminal (re-run with --yes)",
+                    name
+                );
+            }
+            if !add::ask(&format!("Allow {} to run this hook?", name))? {
+                warn("TRUST", &format!("hook of {} not approved", name));
+                continue;
+            }
+        }
+        db.approve_hook(&name, hook);
+        info("TRUST", &format!("approved hook of {}", name));
+        approved += 1;
+    }
+
+    if approved > 0 {
+        db.save()?;
+    }
+    Ok(())
+}
+
+/// Point out a stored hook that will not run until it is approved. For paths
+/// that store templates without asking, like `sync` and `store import`.
+pub fn warn_unapproved(tpl: &Template, db: &Store, scope: &str) {
+    let Some(cmd) = &tpl.hooks.reload else {
+        return;
+    };
+    let name = &tpl.manifest.name;
+    if db.approved_hooks().get(name) != Some(&processor::hook_digest(cmd)) {
+        warn(
+            scope,
+            &format!(
+                "{} has a reload hook that will not run until approved: {} (review it with `hyprink trust {}`)",
+                name, cmd, name
+            ),
+        );
+    }
+}
@@ -0,0 +1,211 @@
+use super::super::logging::{debug, error, info, warn};
+use super::{add, trust};
+use crate::config::SignaturePolicy;
+use crate::db::Store;
+use crate::digest;
+use crate::template::{Template, parse_version};
+use anyhow::{Result, anyhow};
+use std::cmp::Ordering;
+
+pub fn execute(
+    name: Option<String>,
+    allow_downgrade: bool,
+    db: &mut Store,
+    signatures: SignaturePolicy,
+) -> Result<()> {
+    let names: Vec<String> = match name {
+        Some(name) if !db.contains(&name) => {
+            return Err(anyhow!("template '{}' not found", name));
+        }
+        Some(name) => vec![name],
+        None => db.list().iter().map(|t| t.manifest.name.clone()).collect(),
+    };
+
+    let mut dirty = false;
+    let mut changed = Vec::new();
+    let mut updated = 0;
+    let mut current = 0;
+    let mut refused = 0;
+
+    for name in names {
+        let Some(prov) = db.provenance(&name).cloned() else {
+            warn(
+                "UPDATE",
+                &format!("{}: no recorded source, re-add it to track updates", name),
+            );
+            continue;
+        };
+        if !prov.source.exists() {
+            warn(
+                "UPDATE",
+                &format!(
+                    "{}: source {} no longer exists",
+                    name,
+                    prov.source.display()
+                ),
+            );
+            continue;
+        }
+
+        let checksum = match digest::sha256_file(&prov.source) {
+            Ok(checksum) => checksum,
+            Err(e) => {
+                error("UPDATE", &format!("skipping {}: {:#}", name, e));
+                refused += 1;
+                continue;
+            }
+        };
+        if checksum == prov.checksum {
+            debug("UPDATE", &format!("{} is up to date", name));
+            current += 1;
+            continue;
+        }
+
+        // The package may have been swapped since it was added
+        if prov.source.extension().is_some_and(|ext| ext == "pkg")
+            && let Err(e) = add::check_signature(&prov.source, signatures)
+        {
+            error("UPDATE", &format!("skipping {}: {:#}", name, e));
+            refused += 1;
+            continue;
+        }
+
+        let parsed = match add::read_source(&prov.source) {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                error("UPDATE", &format!("skipping {}: {:#}", name, e));
+                refused += 1;
+                continue;
+            }
+        };
+        let candidate = parsed
+            .into_iter()
+            .find(|parsed| parsed.template.manifest.name == name);
+        let Some(parsed) = candidate else {
+            warn(
+                "UPDATE",
+                &format!("{}: no longer found in {}", name, prov.source.display()),
+            );
+            continue;
+        };
+
+        let provenance = parsed.provenance(&prov.source, &checksum, chrono::Utc::now().timestamp());
+        let mut tpl = parsed.template;
+        let stored = db.get(&name).expect("name taken from store");
+        tpl.manifest.ignored = stored.manifest.ignored;
+        let old_version = stored.manifest.version.clone();
+        let new_version = tpl.manifest.version.clone();
+        if same_content(stored, &tpl) {
+            db.set_provenance(&name, provenance);
+            dirty = true;
+            current += 1;
+            continue;
+        }
+
+        match compare_versions(&old_version, &new_version) {
+            None if !allow_downgrade => {
+                error(
+                    "UPDATE",
+                    &format!(
+                        "refusing to update {}: cannot compare v{} with v{} (use --allow-downgrade)",
+                        name, old_version, new_version
+                    ),
+                );
+                refused += 1;
+                continue;
+            }
+            None => warn(
+                "UPDATE",
+                &format!(
+                    "{}: cannot compare v{} with v{}, replacing anyway",
+                    name, old_version, new_version
+                ),
+            ),
+            Some(Ordering::Less) => info(
+                "UPDATE",
+                &format!("{} v{} -> v{}", name, old_version, new_version),
+            ),
+            Some(Ordering::Equal) => info(
+                "UPDATE",
+                &format!("{} changed (still v{})", name, new_version),
+            ),
+            Some(Ordering::Greater) if !allow_downgrade => {
+                error(
+                    "UPDATE",
+                    &format!(
+                        "refusing to downgrade {} v{} -> v{} (use --allow-downgrade)",
+                        name, old_version, new_version
+                    ),
+                );
+                refused += 1;
+                continue;
+            }
+            Some(Ordering::Greater) => warn(
+                "UPDATE",
+                &format!("downgrading {} v{} -> v{}", name, old_version, new_version),
+            ),
+        }
+
+        let conflicts = db.conflicts_with(&tpl);
+        if let Some(conflict) = conflicts.first() {
+            error(
+                "UPDATE",
+                &format!(
+                    "skipping {}: {} is also written by {}",
+                    name,
+                    conflict.path.display(),
+                    conflict.templates.join(", ")
+                ),
+            );
+            refused += 1;
+            continue;
+        }
+
+        trust::warn_unapproved(&tpl, db, "UPDATE");
+        changed.push(tpl.clone());
+        db.add(tpl)?;
+        db.set_provenance(&name, provenance);
+        dirty = true;
+        updated += 1;
+    }
+
+    if dirty {
+        // New versions may depend on missing templates or form a cycle
+        add::check_requires(&changed, db, "UPDATE")?;
+        db.save()?;
+    }
+
+    if refused > 0 {
+        warn(
+            "UPDATE",
+            &format!("{} templates not updated (see errors above)", refused),
+        );
+    }
+    info(
+        "UPDATE",
+        &format!("updated {} templates ({} up to date)", updated, current),
+    );
+    if updated > 0 {
+        info("UPDATE", "run `hyprink apply` to write the changes");
+    }
+
+    Ok(())
+}
+
+/// Compare manifest versions as semver, falling back to plain string equality.
+/// `None` when either is not semver and they differ.
+fn compare_versions(old: &str, new: &str) -> Option<Ordering> {
+    match (parse_version(old), parse_version(new)) {
+        (Some(old), Some(new)) => Some(old.cmp(&new)),
+        _ if old == new => Some(Ordering::Equal),
+        _ => None,
+    }
+}
+
+/// Whether two templates would serialize identically.
+pub fn same_content(a: &Template, b: &Template) -> bool {
+    match (toml::to_string(a), toml::to_string(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
@@ -0,0 +1,146 @@
+use super::super::lock::{self, LockMode, Wait};
+use super::super::logging::{debug, error, info, warn};
+use super::super::output::OutputFormat;
+use super::{apply, sync, theme};
+use crate::config::{self, Config};
+use crate::db::Store;
+use crate::paths::PathPolicy;
+use crate::processor::ApplyOptions;
+use crate::status::{self, TargetState};
+use crate::themes;
+use crate::watch::Watcher;
+use anyhow::{Context, Result, anyhow};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// What a burst of file events touched.
+struct Changes {
+    config: bool,
+    sources: bool,
+    /// Another theme was selected, or the selected one edited
+    theme: bool,
+}
+
+pub fn execute(
+    db_path: &Path,
+    debounce_ms: u64,
+    root: Option<PathBuf>,
+    format: OutputFormat,
+) -> Result<()> {
+    let conf_path = config::resolve_config_path();
+    let conf_dir = conf_path
+        .parent()
+        .ok_or_else(|| anyhow!("Config path has no parent: {:?}", conf_path))?
+        .to_path_buf();
+    let hyprink_d = conf_dir.join("hyprink.d");
+    let themes_dir = config::themes_dir();
+    let mut watcher = Watcher::new().context("Failed to initialise inotify")?;
+
+    info(
+        "WATCH",
+        &format!("watching {} (Ctrl-C to stop)", conf_path.display()),
+    );
+
+    loop {
+        // Synced directories can change between bursts, so re-read them each time
+        let synced = Store::load(db_path)?.synced_dirs().to_vec();
+        watcher
+            .add_dir(&conf_dir)
+            .with_context(|| format!("Failed to watch {}", conf_dir.display()))?;
+        for dir in [&hyprink_d, &themes_dir].into_iter().chain(&synced) {
+            if dir.is_dir()
+                && let Err(e) = watcher.add_dir(dir)
+            {
+                warn("WATCH", &format!("cannot watch {}: {}", dir.display(), e));
+            }
+        }
+
+        let paths = watcher.wait_debounced(Duration::from_millis(debounce_ms))?;
+        let selection = themes::selection_files(&themes_dir);
+        let changes = Changes {
+            config: paths
+                .iter()
+                .any(|p| *p == conf_path || p.starts_with(&hyprink_d)),
+            sources: paths
+                .iter()
+                .any(|p| is_template_file(p) && synced.iter().any(|d| p.starts_with(d))),
+            theme: paths.iter().any(|p| selection.contains(p)),
+        };
+        if !changes.config && !changes.sources && !changes.theme {
+            continue;
+        }
+        debug("WATCH", &format!("changed: {:?}", paths));
+
+        if let Err(e) = refresh(db_path, &conf_path, &changes, root.clone(), format) {
+            error("WATCH", &format!("{:#}", e));
+        }
+    }
+}
+
+/// Recompile and sync as needed, then apply templates whose output changed.
+fn refresh(
+    db_path: &Path,
+    conf_path: &Path,
+    changes: &Changes,
+    root: Option<PathBuf>,
+    format: OutputFormat,
+) -> Result<()> {
+    let _lock = lock::acquire(LockMode::Exclusive, Wait::Forever)?;
+    let mut db = Store::load(db_path)?;
+
+    let mut config = if changes.config {
+        let config = Config::load_from_path(conf_path)
+            .with_context(|| format!("Failed to load {}", conf_path.display()))?;
+        config
+            .save_cache(&config::cache_file())
+            .context("Failed to save config cache")?;
+        info("WATCH", "config changed, cache recompiled");
+        config
+    } else {
+        Config::load().context("Failed to load hyprink config")?
+    };
+    theme::apply_selected(&mut config);
+    if changes.theme {
+        info("WATCH", "theme selection changed");
+    }
+
+    if changes.sources {
+        sync::execute(None, false, &mut db, config.packages.signatures)?;
+    }
+
+    let names = changed_templates(&db, &config);
+    if names.is_empty() {
+        info("WATCH", "no rendered output changed");
+        return Ok(());
+    }
+
+    let opts = ApplyOptions {
+        force: false,
+        policy: PathPolicy::from_config(&config.paths).with_staging(root),
+        ..Default::default()
+    };
+    apply::execute(&mut db, &config, &opts, format, Some(&names))
+}
+
+/// Enabled templates with a target whose render no longer matches the last write.
+fn changed_templates(db: &Store, config: &Config) -> Vec<String> {
+    let policy = PathPolicy::from_config(&config.paths).without_staging();
+    db.list()
+        .into_iter()
+        .filter(|tpl| !tpl.manifest.ignored)
+        .filter(|tpl| {
+            status::template_status(tpl, db, config, &policy)
+                .iter()
+                .any(|t| {
+                    t.error.is_none()
+                        && matches!(t.state, TargetState::Stale | TargetState::Unapplied)
+                })
+        })
+        .map(|tpl| tpl.manifest.name.clone())
+        .collect()
+}
+
+fn is_template_file(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext == "tpl" || ext == "pkg")
+}
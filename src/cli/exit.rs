@@ -0,0 +1,67 @@
+//! Process exit codes other than the generic failure (1).
+//!
+//! Errors carry their code as a [`Failure`], either as the error itself or as
+//! context attached somewhere up the chain. Anything else exits with
+//! [`FAILURE`].
+
+use std::fmt;
+
+/// Any error without a more specific code.
+pub const FAILURE: i32 = 1;
+/// At least one template failed to render or could not be written.
+pub const RENDER_FAILED: i32 = 3;
+/// Every template was written, but at least one reload hook failed.
+pub const HOOK_FAILED: i32 = 4;
+/// Templates were held back: their targets were edited by hand, conflict with
+/// another template, or depend on a template that is not installed. Also used
+/// when a reload hook was not run because it is not approved.
+pub const HELD_BACK: i32 = 5;
+/// The global lock could not be acquired (sysexits `EX_TEMPFAIL`).
+pub const LOCK_UNAVAILABLE: i32 = 75;
+/// The config could not be loaded (sysexits `EX_CONFIG`).
+pub const CONFIG_ERROR: i32 = 78;
+
+/// An error message paired with the exit code it should end the process with.
+#[derive(Debug)]
+pub struct Failure {
+    pub code: i32,
+    message: String,
+}
+
+impl Failure {
+    pub fn new(code: i32, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for Failure {}
+
+/// The code `err` should exit with.
+pub fn code_of(err: &anyhow::Error) -> i32 {
+    err.downcast_ref::<Failure>().map_or(FAILURE, |f| f.code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::{Context, anyhow};
+
+    #[test]
+    fn test_code_survives_context() {
+        let err = Err::<(), _>(anyhow!("parse error"))
+            .context(Failure::new(CONFIG_ERROR, "Failed to load hyprink config"))
+            .context("while starting")
+            .unwrap_err();
+        assert_eq!(code_of(&err), CONFIG_ERROR);
+        assert_eq!(code_of(&anyhow!("plain")), FAILURE);
+    }
+}
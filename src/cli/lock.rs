@@ -0,0 +1,133 @@
+//! Single-instance lock shared by every hyprink process.
+//!
+//! Commands that only read the store take a shared lock, so several of them
+//! can run at once; anything that writes takes it exclusively, including a
+//! read whose load has to migrate or recover the store.
+
+use std::env;
+use std::fs;
+use std::io;
+use std::os::unix::io::AsRawFd;
+use std::path::PathBuf;
+use std::thread;
+use std::time::{Duration, Instant};
+use thiserror::Error;
+use tracing::debug;
+
+/// Interval between attempts while waiting with a timeout.
+const RETRY_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockMode {
+    Shared,
+    Exclusive,
+}
+
+/// How long to wait for a lock held by another instance.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Wait {
+    No,
+    For(Duration),
+    Forever,
+}
+
+impl Wait {
+    /// From `--wait[=SECONDS]`: absent, bare, or with a timeout.
+    pub fn from_arg(arg: Option<Option<u64>>) -> Self {
+        match arg {
+            None => Wait::No,
+            Some(None) => Wait::Forever,
+            Some(Some(secs)) => Wait::For(Duration::from_secs(secs)),
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum LockError {
+    #[error(
+        "another hyprink instance holds {path:?}; retry with --wait[=SECONDS] to queue behind it"
+    )]
+    Busy { path: PathBuf },
+    #[error("timed out after {waited:?} waiting for {path:?}")]
+    TimedOut { path: PathBuf, waited: Duration },
+    #[error("failed to lock {path:?}: {source}")]
+    Io { path: PathBuf, source: io::Error },
+}
+
+/// `$XDG_RUNTIME_DIR`, or the temp dir where there is none.
+pub fn runtime_dir() -> PathBuf {
+    directories::BaseDirs::new()
+        .and_then(|d| d.runtime_dir().map(|p| p.to_path_buf()))
+        .unwrap_or_else(env::temp_dir)
+}
+
+/// Take the global lock in `$XDG_RUNTIME_DIR/hyprink.lock`. The lock is
+/// released when the returned file is dropped.
+pub fn acquire(mode: LockMode, wait: Wait) -> Result<fs::File, LockError> {
+    let runtime_dir = runtime_dir();
+
+    debug!("Using runtime directory for lock: {:?}", runtime_dir);
+
+    if !runtime_dir.exists() {
+        let _ = fs::create_dir_all(&runtime_dir);
+    }
+
+    let path = runtime_dir.join("hyprink.lock");
+    let file = fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&path)
+        .map_err(|source| LockError::Io {
+            path: path.clone(),
+            source,
+        })?;
+
+    let op = match mode {
+        LockMode::Shared => libc::LOCK_SH,
+        LockMode::Exclusive => libc::LOCK_EX,
+    };
+    let fd = file.as_raw_fd();
+    let try_lock = |op: i32| {
+        if unsafe { libc::flock(fd, op) } == 0 {
+            Ok(true)
+        } else {
+            let err = io::Error::last_os_error();
+            match err.raw_os_error() {
+                Some(libc::EWOULDBLOCK) => Ok(false),
+                Some(libc::EINTR) => Ok(false),
+                _ => Err(err),
+            }
+        }
+    };
+    let io_err = |source| LockError::Io {
+        path: path.clone(),
+        source,
+    };
+
+    let started = Instant::now();
+    loop {
+        let locked = match wait {
+            Wait::Forever => try_lock(op),
+            Wait::No | Wait::For(_) => try_lock(op | libc::LOCK_NB),
+        }
+        .map_err(io_err)?;
+        if locked {
+            debug!("Acquired {:?} lock after {:?}", mode, started.elapsed());
+            return Ok(file);
+        }
+
+        match wait {
+            Wait::No => return Err(LockError::Busy { path }),
+            Wait::For(timeout) if started.elapsed() >= timeout => {
+                return Err(LockError::TimedOut {
+                    path,
+                    waited: timeout,
+                });
+            }
+            Wait::For(_) => thread::sleep(RETRY_INTERVAL),
+            // Interrupted by a signal; block again
+            Wait::Forever => {}
+        }
+    }
+}
@@ -1,4 +1,8 @@
 pub mod args;
 pub mod cli_config;
 pub mod commands;
+pub mod exit;
+pub mod lock;
 pub mod logging;
+pub mod output;
+pub mod rpc;
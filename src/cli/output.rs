@@ -0,0 +1,42 @@
+//! Output format selection for commands that can report as JSON.
+
+use anyhow::Result;
+use clap::ValueEnum;
+use serde::Serialize;
+
+/// Version of the JSON documents printed with `--format json`.
+///
+/// Bump it when a field is removed or changes meaning; adding fields is compatible.
+pub const JSON_SCHEMA: u32 = 1;
+
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+impl OutputFormat {
+    pub fn is_json(self) -> bool {
+        self == OutputFormat::Json
+    }
+}
+
+#[derive(Serialize)]
+struct Document<'a, T: Serialize> {
+    schema: u32,
+    command: &'a str,
+    #[serde(flatten)]
+    body: &'a T,
+}
+
+/// Print `body` as a single JSON document on stdout, tagged with the schema version.
+pub fn print_json<T: Serialize>(command: &str, body: &T) -> Result<()> {
+    let doc = Document {
+        schema: JSON_SCHEMA,
+        command,
+        body,
+    };
+    println!("{}", serde_json::to_string_pretty(&doc)?);
+    Ok(())
+}
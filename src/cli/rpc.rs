@@ -0,0 +1,191 @@
+//! Wire format of the daemon's control socket.
+//!
+//! Each message is one JSON object per line. Clients send requests
+//! `{"id": 1, "method": "apply", "params": {...}}` and get back
+//! `{"id": 1, "result": ...}` or `{"id": 1, "error": {"message": "..."}}`.
+//! After a `subscribe` request the connection also receives events
+//! `{"event": "applied", "data": ...}` as they happen.
+
+use super::lock;
+use super::logging::warn;
+use anyhow::{Context, Result, anyhow};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::io::{BufRead, BufReader, Write};
+#[cfg(target_os = "linux")]
+use std::os::fd::AsRawFd;
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+
+/// Set to bypass a running daemon and work on the store directly.
+pub const NO_DAEMON_ENV: &str = "HYPRINK_NO_DAEMON";
+
+pub fn socket_path() -> PathBuf {
+    lock::runtime_dir().join("hyprink.sock")
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Request {
+    pub id: u64,
+    pub method: String,
+    #[serde(default)]
+    pub params: Value,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RpcError {
+    pub message: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Response {
+    pub id: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<RpcError>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Event {
+    pub event: String,
+    pub data: Value,
+}
+
+/// Anything the daemon writes to a connection.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum Message {
+    Response(Response),
+    Event(Event),
+}
+
+/// Write `value` as a single line.
+pub fn send<T: Serialize>(stream: &mut UnixStream, value: &T) -> std::io::Result<()> {
+    let mut line = serde_json::to_vec(value)?;
+    line.push(b'\n');
+    stream.write_all(&line)
+}
+
+pub struct Client {
+    reader: BufReader<UnixStream>,
+    writer: UnixStream,
+    next_id: u64,
+}
+
+impl Client {
+    /// Connect to a running daemon; `None` if there is none or it is bypassed.
+    pub fn connect() -> Option<Self> {
+        if std::env::var_os(NO_DAEMON_ENV).is_some_and(|v| !v.is_empty()) {
+            return None;
+        }
+        let path = socket_path();
+        let stream = UnixStream::connect(&path).ok()?;
+        // Without $XDG_RUNTIME_DIR the socket lives in the shared temp dir
+        if !peer_is_current_user(&stream, &path) {
+            warn(
+                "DAEMON",
+                &format!("ignoring {}: it is served by another user", path.display()),
+            );
+            return None;
+        }
+        let writer = stream.try_clone().ok()?;
+        Some(Self {
+            reader: BufReader::new(stream),
+            writer,
+            next_id: 1,
+        })
+    }
+
+    /// Send a request and wait for its response, skipping any events.
+    pub fn call(&mut self, method: &str, params: Value) -> Result<Value> {
+        let id = self.next_id;
+        self.next_id += 1;
+        send(
+            &mut self.writer,
+            &Request {
+                id,
+                method: method.to_string(),
+                params,
+            },
+        )
+        .context("Failed to send request to the daemon")?;
+
+        loop {
+            match self.read()? {
+                Some(Message::Response(resp)) if resp.id == id => {
+                    return match resp.error {
+                        Some(err) => Err(anyhow!("daemon: {}", err.message)),
+                        None => Ok(resp.result.unwrap_or(Value::Null)),
+                    };
+                }
+                Some(_) => continue,
+                None => return Err(anyhow!("daemon closed the connection")),
+            }
+        }
+    }
+
+    /// Next event on a subscribed connection; `None` once the daemon is gone.
+    pub fn next_event(&mut self) -> Result<Option<Event>> {
+        loop {
+            match self.read()? {
+                Some(Message::Event(event)) => return Ok(Some(event)),
+                Some(Message::Response(_)) => continue,
+                None => return Ok(None),
+            }
+        }
+    }
+
+    fn read(&mut self) -> Result<Option<Message>> {
+        let mut line = String::new();
+        if self.reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let msg = serde_json::from_str(&line).context("Malformed message from the daemon")?;
+        Ok(Some(msg))
+    }
+}
+
+/// Whether the process on the other end of `stream` runs as the current user.
+#[cfg(target_os = "linux")]
+fn peer_is_current_user(stream: &UnixStream, _path: &Path) -> bool {
+    let mut cred = libc::ucred {
+        pid: 0,
+        uid: 0,
+        gid: 0,
+    };
+    let mut len = std::mem::size_of::<libc::ucred>() as libc::socklen_t;
+    let ret = unsafe {
+        libc::getsockopt(
+            stream.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_PEERCRED,
+            (&mut cred as *mut libc::ucred).cast(),
+            &mut len,
+        )
+    };
+    ret == 0 && cred.uid == unsafe { libc::geteuid() }
+}
+
+/// Without peer credentials, whether the socket file belongs to the current user.
+#[cfg(not(target_os = "linux"))]
+fn peer_is_current_user(_stream: &UnixStream, path: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    std::fs::metadata(path).is_ok_and(|m| m.uid() == unsafe { libc::geteuid() })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::net::UnixListener;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_own_socket_is_trusted() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("hyprink.sock");
+        let _listener = UnixListener::bind(&path).unwrap();
+        let stream = UnixStream::connect(&path).unwrap();
+        assert!(peer_is_current_user(&stream, &path));
+    }
+}
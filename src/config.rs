@@ -1,7 +1,8 @@
+use crate::fonts::{self, Font};
+use hypr_conf::{ConfigMetaSpec, file_matches, resolve_config_path_strict};
 use log::debug;
 use serde::{Deserialize, Serialize};
-use hypr_conf::{ConfigMetaSpec, file_matches, resolve_config_path_strict};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
 use std::path::{Path, PathBuf};
 use thiserror::Error;
@@ -20,13 +21,84 @@ pub struct ThemeSection {
     pub name: String,
     pub active_icons: String,
     pub colors: HashMap<String, String>,
+    /// Plain font strings: `<role>` is a family, `size_<role>` its size
     pub fonts: HashMap<String, String>,
+    /// Typed font roles, one `[theme.font.<role>]` table each
+    #[serde(default)]
+    pub font: HashMap<String, Font>,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+impl ThemeSection {
+    /// Every font role, typed or from the plain `fonts` strings.
+    pub fn font_roles(&self) -> BTreeMap<String, Font> {
+        fonts::roles(&self.fonts, &self.font)
+    }
+
+    /// The plain `fonts` strings, including the typed roles.
+    pub fn font_strings(&self) -> BTreeMap<String, String> {
+        fonts::strings(&self.fonts, &self.font)
+    }
+}
+
+/// Key inside an `[icons.<set>]` table naming the set to take missing icons from.
+pub const ICON_FALLBACK_KEY: &str = "fallback";
+/// Set used when `active_icons` names a set that does not exist.
+pub const DEFAULT_ICON_SET: &str = "ascii";
+
+/// Icon sets by name, one `[icons.<set>]` table each.
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(transparent)]
 pub struct IconsSection {
-    pub nerdfont: HashMap<String, String>,
-    pub ascii: HashMap<String, String>,
+    pub sets: HashMap<String, HashMap<String, String>>,
+}
+
+impl IconsSection {
+    /// The set `name` names as its fallback, if any.
+    pub fn fallback(&self, name: &str) -> Option<&str> {
+        self.sets
+            .get(name)?
+            .get(ICON_FALLBACK_KEY)
+            .map(String::as_str)
+    }
+
+    /// `active` followed by its fallbacks, as far as they exist and do not
+    /// loop. An unknown `active` starts from [`DEFAULT_ICON_SET`] instead.
+    pub fn chain<'a>(&'a self, active: &'a str) -> Vec<&'a str> {
+        let mut chain = Vec::new();
+        let mut next = if self.sets.contains_key(active) {
+            Some(active)
+        } else {
+            Some(DEFAULT_ICON_SET)
+        };
+        while let Some(name) = next {
+            if !self.sets.contains_key(name) || chain.contains(&name) {
+                break;
+            }
+            chain.push(name);
+            next = self.fallback(name);
+        }
+        chain
+    }
+
+    /// The icons of `active`, with every icon it lacks taken from the first
+    /// set down its fallback chain that has it.
+    pub fn resolve(&self, active: &str) -> HashMap<String, String> {
+        let mut icons = HashMap::new();
+        for name in self.chain(active).into_iter().rev() {
+            icons.extend(
+                self.sets[name]
+                    .iter()
+                    .filter(|(key, _)| *key != ICON_FALLBACK_KEY)
+                    .map(|(key, glyph)| (key.clone(), glyph.clone())),
+            );
+        }
+        icons
+    }
+
+    /// Whether any set defines icon `key`.
+    pub fn defines(&self, key: &str) -> bool {
+        key != ICON_FALLBACK_KEY && self.sets.values().any(|set| set.contains_key(key))
+    }
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -97,13 +169,90 @@ fn default_compress_after_days() -> Option<u32> {
     Some(7)
 }
 
+#[derive(Debug, Deserialize, Serialize, Default)]
+pub struct PathsSection {
+    /// Extra directories templates may write into, besides $HOME and the XDG dirs
+    #[serde(default)]
+    pub allowed_roots: Vec<String>,
+    /// Permit targets outside every allowed root
+    #[serde(default)]
+    pub allow_outside: bool,
+}
+
+/// How `add` treats package signatures.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SignaturePolicy {
+    /// Refuse packages not signed by a trusted key
+    Require,
+    /// Install unsigned or untrusted packages with a warning
+    #[default]
+    Warn,
+    /// Do not look at signatures
+    Off,
+}
+
+#[derive(Debug, Deserialize, Serialize, Default)]
+pub struct PackagesSection {
+    #[serde(default)]
+    pub signatures: SignaturePolicy,
+}
+
+/// A `[theme]` table (and optionally `[icons.*]` tables) from another file,
+/// laid over the configured theme.
+///
+/// Every field is optional so a palette file only needs the keys it changes.
+#[derive(Debug, Default, Deserialize)]
+pub struct ThemeFile {
+    #[serde(default)]
+    theme: ThemeOverlay,
+    /// Icon sets to add, or icons to add to existing sets
+    #[serde(default)]
+    icons: HashMap<String, HashMap<String, String>>,
+}
+
+impl ThemeFile {
+    pub fn parse(content: &str) -> Result<Self, ConfigError> {
+        Ok(toml::from_str(content)?)
+    }
+
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        if !path.exists() {
+            return Err(ConfigError::ConfigFileNotFound(path.to_path_buf()));
+        }
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    /// The `name` the file gives its theme, if any.
+    pub fn name(&self) -> Option<&str> {
+        self.theme.name.as_deref()
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct ThemeOverlay {
+    name: Option<String>,
+    active_icons: Option<String>,
+    #[serde(default)]
+    colors: HashMap<String, String>,
+    #[serde(default)]
+    fonts: HashMap<String, String>,
+    #[serde(default)]
+    font: HashMap<String, Font>,
+}
+
 // === Main Config ===
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Config {
     pub theme: ThemeSection,
+    #[serde(default)]
     pub icons: IconsSection,
     pub layout: LayoutSection,
+    #[serde(default)]
+    pub paths: PathsSection,
+    #[serde(default)]
+    pub packages: PackagesSection,
 }
 
 // === Errors ===
@@ -166,6 +315,25 @@ pub fn data_dir() -> PathBuf {
         .join("hyprink")
 }
 
+/// Public keys whose package signatures `add` accepts.
+pub fn trusted_keys_dir() -> PathBuf {
+    config_path()
+        .parent()
+        .map(Path::to_path_buf)
+        .unwrap_or_default()
+        .join("hyprink/trusted-keys")
+}
+
+/// Themes installed from packages, one `<name>.toml` each.
+pub fn themes_dir() -> PathBuf {
+    data_dir().join("themes")
+}
+
+/// Key pairs made by `hyprink keygen`.
+pub fn keys_dir() -> PathBuf {
+    data_dir().join("keys")
+}
+
 pub fn cache_file() -> PathBuf {
     cache_dir().join("config.bin")
 }
@@ -245,6 +413,35 @@ impl Config {
         Ok(config)
     }
 
+    /// Override theme values with the `[theme]` table of another file.
+    /// Colors and fonts it does not mention keep their configured values.
+    pub fn overlay_theme(&mut self, path: &Path) -> Result<(), ConfigError> {
+        self.overlay(ThemeFile::load(path)?);
+        debug!("Applied theme overlay from: {:?}", path);
+        Ok(())
+    }
+
+    /// Lay a parsed theme file over the configured theme and icons.
+    pub fn overlay(&mut self, file: ThemeFile) {
+        let overlay = file.theme;
+        if let Some(name) = overlay.name {
+            self.theme.name = name;
+        }
+        if let Some(icons) = overlay.active_icons {
+            self.theme.active_icons = icons;
+        }
+        self.theme.colors.extend(overlay.colors);
+        // A plain family from the theme replaces a typed role of the same name
+        for role in overlay.fonts.keys() {
+            self.theme.font.remove(role);
+        }
+        self.theme.fonts.extend(overlay.fonts);
+        self.theme.font.extend(overlay.font);
+        for (name, icons) in file.icons {
+            self.icons.sets.entry(name).or_default().extend(icons);
+        }
+    }
+
     pub fn save_cache(&self, path: &Path) -> Result<(), ConfigError> {
         if let Some(parent) = path.parent() {
             fs::create_dir_all(parent)?;
@@ -305,3 +502,28 @@ fn discover_metadata_config(config_dir: &Path) -> Option<PathBuf> {
     let fallback = config_dir.join("hyprink.conf");
     resolve_config_path_strict(config_dir, &fallback, &config_meta_spec())
 }
+
+/// A minimal config for unit tests: two colors (`fg` is not hex) and an unknown icon set.
+#[cfg(test)]
+pub(crate) fn test_config() -> Config {
+    toml::from_str(
+        r##"
+[theme]
+name = "test"
+active_icons = "emoji"
+colors = { bg = "#1e1e2e", fg = "white" }
+fonts = {}
+
+[icons]
+nerdfont = {}
+ascii = {}
+
+[layout]
+labels = {}
+tag = { prefix = "", suffix = "", transform = "none", min_width = 0, alignment = "left" }
+structure = { terminal = "", file = "" }
+logging = { base_dir = "", path_structure = "", filename_structure = "", timestamp_format = "", write_by_default = false }
+"##,
+    )
+    .unwrap()
+}
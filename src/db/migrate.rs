@@ -0,0 +1,597 @@
+//! On-disk layout of `store.db`. Every file starts with [`MAGIC`] followed by
+//! the schema version as a little-endian `u32`. From schema 2 on, a SHA-256
+//! of the payload follows, then the bincode payload itself. Files without the
+//! header predate versioning and are treated as schema 0.
+//!
+//! Older schemas keep a frozen copy of their types here so they can still be
+//! decoded after `Template` changes; bump [`SCHEMA_VERSION`] whenever the
+//! encoded shape of `StoreData` changes and add a decoder for the previous one.
+
+use super::StoreData;
+use crate::digest;
+use serde::de::DeserializeOwned;
+use std::collections::HashMap;
+use thiserror::Error;
+
+pub const MAGIC: &[u8; 8] = b"HYPRINK\0";
+pub const SCHEMA_VERSION: u32 = 6;
+const CHECKSUM_LEN: usize = 32;
+
+#[derive(Error, Debug)]
+pub enum FormatError {
+    #[error("store header is truncated")]
+    Truncated,
+    #[error("checksum mismatch")]
+    ChecksumMismatch,
+    #[error("failed to decode store payload: {0}")]
+    Decode(String),
+    #[error("failed to encode store payload: {0}")]
+    Encode(String),
+    #[error(
+        "store schema v{found} is newer than this hyprink supports (v{supported}); upgrade hyprink"
+    )]
+    Newer { found: u32, supported: u32 },
+    #[error("unknown store schema v{0}")]
+    Unknown(u32),
+}
+
+impl FormatError {
+    /// Whether the file is damaged, as opposed to written by a newer hyprink.
+    pub fn is_corruption(&self) -> bool {
+        !matches!(self, Self::Newer { .. } | Self::Encode(_))
+    }
+}
+
+/// Encode `data` with the current header and checksum.
+pub fn encode(data: &StoreData) -> Result<Vec<u8>, FormatError> {
+    let payload = bincode::serde::encode_to_vec(data, bincode::config::standard())
+        .map_err(|e| FormatError::Encode(e.to_string()))?;
+    let mut out = Vec::with_capacity(MAGIC.len() + 4 + CHECKSUM_LEN + payload.len());
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&SCHEMA_VERSION.to_le_bytes());
+    out.extend_from_slice(&digest::sha256(&payload));
+    out.extend_from_slice(&payload);
+    Ok(out)
+}
+
+/// Decode a complete store file, returning the schema it was written with and
+/// its contents upgraded to the current layout.
+pub fn decode(bytes: &[u8]) -> Result<(u32, StoreData), FormatError> {
+    let Some(rest) = bytes.strip_prefix(MAGIC.as_slice()) else {
+        let legacy = decode_payload::<HashMap<String, v0::Template>>(bytes)?;
+        return Ok((0, from_v4(v0::migrate(legacy))));
+    };
+    if rest.len() < 4 {
+        return Err(FormatError::Truncated);
+    }
+    let (version, rest) = rest.split_at(4);
+    let version = u32::from_le_bytes([version[0], version[1], version[2], version[3]]);
+
+    match version {
+        1 => Ok((1, from_v2(decode_payload(rest)?))),
+        2 => Ok((2, from_v2(decode_payload(verified(rest)?)?))),
+        3 => Ok((3, from_v3(decode_payload(verified(rest)?)?))),
+        4 => Ok((4, from_v4(decode_payload(verified(rest)?)?))),
+        5 => Ok((5, v5::migrate(decode_payload(verified(rest)?)?))),
+        SCHEMA_VERSION => Ok((SCHEMA_VERSION, decode_payload(verified(rest)?)?)),
+        v if v > SCHEMA_VERSION => Err(FormatError::Newer {
+            found: v,
+            supported: SCHEMA_VERSION,
+        }),
+        v => Err(FormatError::Unknown(v)),
+    }
+}
+
+/// The schema a store file was written with, checking the header and
+/// checksum without decoding the payload.
+pub fn schema_version(bytes: &[u8]) -> Result<u32, FormatError> {
+    let Some(rest) = bytes.strip_prefix(MAGIC.as_slice()) else {
+        return Ok(0);
+    };
+    if rest.len() < 4 {
+        return Err(FormatError::Truncated);
+    }
+    let (version, rest) = rest.split_at(4);
+    let version = u32::from_le_bytes([version[0], version[1], version[2], version[3]]);
+    match version {
+        1 => Ok(1),
+        2..=SCHEMA_VERSION => verified(rest).map(|_| version),
+        v => Err(FormatError::Newer {
+            found: v,
+            supported: SCHEMA_VERSION,
+        }),
+    }
+}
+
+fn from_v2(old: v2::StoreData) -> StoreData {
+    from_v3(v2::migrate(old))
+}
+
+fn from_v3(old: v3::StoreData) -> StoreData {
+    from_v4(v3::migrate(old))
+}
+
+fn from_v4(old: v4::StoreData) -> StoreData {
+    v5::migrate(v4::migrate(old))
+}
+
+/// Check the checksum in front of a payload and return the payload.
+fn verified(rest: &[u8]) -> Result<&[u8], FormatError> {
+    if rest.len() < CHECKSUM_LEN {
+        return Err(FormatError::Truncated);
+    }
+    let (checksum, payload) = rest.split_at(CHECKSUM_LEN);
+    if digest::sha256(payload) != checksum {
+        return Err(FormatError::ChecksumMismatch);
+    }
+    Ok(payload)
+}
+
+fn decode_payload<T: DeserializeOwned>(payload: &[u8]) -> Result<T, FormatError> {
+    let (value, read) = bincode::serde::decode_from_slice(payload, bincode::config::standard())
+        .map_err(|e| FormatError::Decode(e.to_string()))?;
+    if read != payload.len() {
+        return Err(FormatError::Decode(format!(
+            "{} trailing bytes",
+            payload.len() - read
+        )));
+    }
+    Ok(value)
+}
+
+/// Schema 5: no package repositories. Its `Template`, `Provenance` and
+/// `WriteRecord` are also what schemas 1 to 4 stored.
+mod v5 {
+    use crate::db;
+    use crate::template;
+    use serde::{Deserialize, Serialize};
+    use std::collections::HashMap;
+    use std::path::PathBuf;
+
+    #[derive(Serialize, Deserialize)]
+    pub struct StoreData {
+        pub templates: HashMap<String, Template>,
+        pub provenance: HashMap<String, Provenance>,
+        pub synced_dirs: Vec<PathBuf>,
+        pub written: HashMap<PathBuf, WriteRecord>,
+        pub approved_hooks: HashMap<String, String>,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct Template {
+        pub manifest: TemplateManifest,
+        pub targets: Vec<Target>,
+        pub files: Vec<Target>,
+        pub hooks: Hooks,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct TemplateManifest {
+        pub name: String,
+        pub version: String,
+        pub authors: Vec<String>,
+        pub description: String,
+        pub repository: Option<String>,
+        pub license: Option<String>,
+        pub ignored: bool,
+        pub depends_on: Vec<String>,
+        pub after: Vec<String>,
+        pub overrides: Vec<String>,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct Target {
+        pub target: String,
+        pub content: String,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct Hooks {
+        pub reload: Option<String>,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct Provenance {
+        pub source: PathBuf,
+        pub entry: Option<String>,
+        pub checksum: String,
+        pub added_at: i64,
+        pub package: Option<PackageRef>,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct PackageRef {
+        pub name: String,
+        pub version: String,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct WriteRecord {
+        pub template: String,
+        pub target: String,
+        pub checksum: String,
+        pub written_at: i64,
+    }
+
+    pub fn migrate(old: StoreData) -> super::StoreData {
+        let provenance = |p: Provenance| db::Provenance {
+            source: p.source,
+            entry: p.entry,
+            checksum: p.checksum,
+            added_at: p.added_at,
+            package: p.package.map(|pkg| db::PackageRef {
+                name: pkg.name,
+                version: pkg.version,
+            }),
+        };
+        let record = |r: WriteRecord| db::WriteRecord {
+            template: r.template,
+            target: r.target,
+            checksum: r.checksum,
+            written_at: r.written_at,
+        };
+        super::StoreData {
+            templates: old
+                .templates
+                .into_iter()
+                .map(|(name, tpl)| (name, upgrade(tpl)))
+                .collect(),
+            provenance: old
+                .provenance
+                .into_iter()
+                .map(|(name, p)| (name, provenance(p)))
+                .collect(),
+            synced_dirs: old.synced_dirs,
+            written: old
+                .written
+                .into_iter()
+                .map(|(path, r)| (path, record(r)))
+                .collect(),
+            approved_hooks: old.approved_hooks,
+            repositories: Vec::new(),
+        }
+    }
+
+    fn upgrade(tpl: Template) -> template::Template {
+        let target = |t: Target| template::Target {
+            target: t.target,
+            content: t.content,
+        };
+        template::Template {
+            manifest: template::TemplateManifest {
+                name: tpl.manifest.name,
+                version: tpl.manifest.version,
+                authors: tpl.manifest.authors,
+                description: tpl.manifest.description,
+                repository: tpl.manifest.repository,
+                license: tpl.manifest.license,
+                ignored: tpl.manifest.ignored,
+                depends_on: tpl.manifest.depends_on,
+                after: tpl.manifest.after,
+                overrides: tpl.manifest.overrides,
+            },
+            targets: tpl.targets.into_iter().map(target).collect(),
+            files: tpl.files.into_iter().map(target).collect(),
+            hooks: template::Hooks {
+                reload: tpl.hooks.reload,
+            },
+        }
+    }
+}
+
+/// Schema 4: no hook approvals. Hooks that were already installed kept
+/// running before approvals existed, so they are approved as they are.
+mod v4 {
+    use super::v5::{self, Provenance, Template, WriteRecord};
+    use crate::processor;
+    use serde::{Deserialize, Serialize};
+    use std::collections::HashMap;
+    use std::path::PathBuf;
+
+    #[derive(Serialize, Deserialize)]
+    pub struct StoreData {
+        pub templates: HashMap<String, Template>,
+        pub provenance: HashMap<String, Provenance>,
+        pub synced_dirs: Vec<PathBuf>,
+        pub written: HashMap<PathBuf, WriteRecord>,
+    }
+
+    pub fn migrate(old: StoreData) -> v5::StoreData {
+        let approved_hooks = old
+            .templates
+            .iter()
+            .filter_map(|(name, tpl)| {
+                let hook = tpl.hooks.reload.as_deref()?;
+                Some((name.clone(), processor::hook_digest(hook)))
+            })
+            .collect();
+        v5::StoreData {
+            templates: old.templates,
+            provenance: old.provenance,
+            synced_dirs: old.synced_dirs,
+            written: old.written,
+            approved_hooks,
+        }
+    }
+}
+
+/// Schema 3: provenance without the package a template came in.
+mod v3 {
+    use super::v4;
+    use super::v5::{self, Template, WriteRecord};
+    use serde::{Deserialize, Serialize};
+    use std::collections::HashMap;
+    use std::path::PathBuf;
+
+    #[derive(Serialize, Deserialize)]
+    pub struct StoreData {
+        pub templates: HashMap<String, Template>,
+        pub provenance: HashMap<String, Provenance>,
+        pub synced_dirs: Vec<PathBuf>,
+        pub written: HashMap<PathBuf, WriteRecord>,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct Provenance {
+        pub source: PathBuf,
+        pub entry: Option<String>,
+        pub checksum: String,
+        pub added_at: i64,
+    }
+
+    pub fn migrate(old: StoreData) -> v4::StoreData {
+        let provenance = old
+            .provenance
+            .into_iter()
+            .map(|(name, p)| {
+                let upgraded = v5::Provenance {
+                    source: p.source,
+                    entry: p.entry,
+                    checksum: p.checksum,
+                    added_at: p.added_at,
+                    package: None,
+                };
+                (name, upgraded)
+            })
+            .collect();
+        v4::StoreData {
+            templates: old.templates,
+            provenance,
+            synced_dirs: old.synced_dirs,
+            written: old.written,
+        }
+    }
+}
+
+/// Schemas 1 and 2: `StoreData` before write records; schema 1 has no checksum.
+mod v2 {
+    use super::v3::{self, Provenance};
+    use super::v5::Template;
+    use serde::{Deserialize, Serialize};
+    use std::collections::HashMap;
+    use std::path::PathBuf;
+
+    #[derive(Serialize, Deserialize)]
+    pub struct StoreData {
+        pub templates: HashMap<String, Template>,
+        pub provenance: HashMap<String, Provenance>,
+        pub synced_dirs: Vec<PathBuf>,
+    }
+
+    pub fn migrate(old: StoreData) -> v3::StoreData {
+        v3::StoreData {
+            templates: old.templates,
+            provenance: old.provenance,
+            synced_dirs: old.synced_dirs,
+            written: Default::default(),
+        }
+    }
+}
+
+/// Schema 0: a bare `HashMap<String, Template>` from before dependencies,
+/// overrides and provenance existed.
+mod v0 {
+    use super::v4::StoreData;
+    use super::v5;
+    use serde::{Deserialize, Serialize};
+    use std::collections::HashMap;
+
+    #[derive(Serialize, Deserialize)]
+    pub struct Template {
+        pub manifest: TemplateManifest,
+        pub targets: Vec<Target>,
+        pub files: Vec<Target>,
+        pub hooks: Hooks,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct TemplateManifest {
+        pub name: String,
+        pub version: String,
+        pub authors: Vec<String>,
+        pub description: String,
+        pub repository: Option<String>,
+        pub license: Option<String>,
+        pub ignored: bool,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct Target {
+        pub target: String,
+        pub content: String,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct Hooks {
+        pub reload: Option<String>,
+    }
+
+    pub fn migrate(old: HashMap<String, Template>) -> StoreData {
+        let templates = old
+            .into_iter()
+            .map(|(name, tpl)| (name, upgrade(tpl)))
+            .collect();
+        StoreData {
+            templates,
+            provenance: HashMap::new(),
+            synced_dirs: Vec::new(),
+            written: HashMap::new(),
+        }
+    }
+
+    fn upgrade(tpl: Template) -> v5::Template {
+        let target = |t: Target| v5::Target {
+            target: t.target,
+            content: t.content,
+        };
+        v5::Template {
+            manifest: v5::TemplateManifest {
+                name: tpl.manifest.name,
+                version: tpl.manifest.version,
+                authors: tpl.manifest.authors,
+                description: tpl.manifest.description,
+                repository: tpl.manifest.repository,
+                license: tpl.manifest.license,
+                ignored: tpl.manifest.ignored,
+                depends_on: vec![],
+                after: vec![],
+                overrides: vec![],
+            },
+            targets: tpl.targets.into_iter().map(target).collect(),
+            files: tpl.files.into_iter().map(target).collect(),
+            hooks: v5::Hooks {
+                reload: tpl.hooks.reload,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Store;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_legacy_store_is_migrated_with_backup() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("store.db");
+
+        let mut legacy = HashMap::new();
+        legacy.insert(
+            "waybar".to_string(),
+            v0::Template {
+                manifest: v0::TemplateManifest {
+                    name: "waybar".to_string(),
+                    version: "0.1.0".to_string(),
+                    authors: vec!["Test".to_string()],
+                    description: "Legacy template".to_string(),
+                    repository: None,
+                    license: None,
+                    ignored: true,
+                },
+                targets: vec![],
+                files: vec![],
+                hooks: v0::Hooks {
+                    reload: Some("true".to_string()),
+                },
+            },
+        );
+        let bytes = bincode::serde::encode_to_vec(&legacy, bincode::config::standard()).unwrap();
+        fs::write(&path, &bytes).unwrap();
+        assert!(Store::needs_repair(&path));
+
+        let db = Store::load(&path).unwrap();
+        let tpl = db.get("waybar").unwrap();
+        assert!(tpl.manifest.ignored);
+        assert_eq!(tpl.hooks.reload.as_deref(), Some("true"));
+        assert_eq!(
+            db.approved_hooks().get("waybar"),
+            Some(&crate::processor::hook_digest("true"))
+        );
+
+        assert_eq!(fs::read(dir.path().join("store.db.v0.bak")).unwrap(), bytes);
+        let upgraded = fs::read(&path).unwrap();
+        assert_eq!(decode(&upgraded).unwrap().0, SCHEMA_VERSION);
+        assert!(!Store::needs_repair(&path));
+    }
+
+    #[test]
+    fn test_v5_store_keeps_templates_and_provenance() {
+        let old = v5::StoreData {
+            templates: HashMap::from([(
+                "bar".to_string(),
+                v5::Template {
+                    manifest: v5::TemplateManifest {
+                        name: "bar".to_string(),
+                        version: "1.0.0".to_string(),
+                        authors: vec![],
+                        description: String::new(),
+                        repository: None,
+                        license: None,
+                        ignored: false,
+                        depends_on: vec!["base".to_string()],
+                        after: vec![],
+                        overrides: vec![],
+                    },
+                    targets: vec![],
+                    files: vec![],
+                    hooks: v5::Hooks { reload: None },
+                },
+            )]),
+            provenance: HashMap::from([(
+                "bar".to_string(),
+                v5::Provenance {
+                    source: "/tmp/bar.pkg".into(),
+                    entry: Some("bar.tpl".to_string()),
+                    checksum: "abc".to_string(),
+                    added_at: 1,
+                    package: Some(v5::PackageRef {
+                        name: "bars".to_string(),
+                        version: "2.0.0".to_string(),
+                    }),
+                },
+            )]),
+            synced_dirs: vec![],
+            written: HashMap::new(),
+            approved_hooks: HashMap::new(),
+        };
+        let payload = bincode::serde::encode_to_vec(&old, bincode::config::standard()).unwrap();
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&5u32.to_le_bytes());
+        bytes.extend_from_slice(&digest::sha256(&payload));
+        bytes.extend_from_slice(&payload);
+
+        let (version, data) = decode(&bytes).unwrap();
+        assert_eq!(version, 5);
+        assert_eq!(data.templates["bar"].manifest.depends_on, vec!["base"]);
+        let package = data.provenance["bar"].package.as_ref().unwrap();
+        assert_eq!(
+            (package.name.as_str(), package.version.as_str()),
+            ("bars", "2.0.0")
+        );
+    }
+
+    #[test]
+    fn test_newer_schema_is_not_corruption() {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&(SCHEMA_VERSION + 1).to_le_bytes());
+        let err = decode(&bytes).unwrap_err();
+        assert!(!err.is_corruption());
+    }
+
+    #[test]
+    fn test_flipped_byte_fails_checksum() {
+        let mut bytes = encode(&StoreData::default()).unwrap();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0xff;
+        assert!(matches!(decode(&bytes), Err(FormatError::ChecksumMismatch)));
+        assert!(matches!(
+            schema_version(&bytes),
+            Err(FormatError::ChecksumMismatch)
+        ));
+        bytes[last] ^= 0xff;
+        assert_eq!(schema_version(&bytes).unwrap(), SCHEMA_VERSION);
+    }
+}
@@ -0,0 +1,608 @@
+mod migrate;
+
+pub use migrate::FormatError;
+
+use crate::logger;
+use crate::paths;
+use crate::resolver::{self, ResolveError};
+use crate::template::Template;
+use anyhow::{Context, Result, anyhow};
+use log::debug;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Number of store snapshots kept next to the database.
+const KEEP_SNAPSHOTS: usize = 5;
+
+/// What [`Store::verify_file`] found in a readable store file.
+#[derive(Debug, Clone, Copy)]
+pub struct StoreFileInfo {
+    pub schema: u32,
+    pub templates: usize,
+}
+
+/// A resolved target path written by several templates, none of which
+/// overrides all of the others.
+#[derive(Debug, Clone)]
+pub struct TargetConflict {
+    pub path: PathBuf,
+    pub templates: Vec<String>,
+}
+
+/// Where a stored template was added from.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Provenance {
+    /// Absolute path of the `.tpl` or `.pkg` file
+    pub source: PathBuf,
+    /// Entry name inside the package, `None` for a plain `.tpl`
+    pub entry: Option<String>,
+    /// SHA-256 of the source file when it was added
+    pub checksum: String,
+    /// Unix timestamp of the add
+    pub added_at: i64,
+    /// The package the template came in, if its `.pkg` had a manifest
+    pub package: Option<PackageRef>,
+}
+
+/// Name and version from a package's `package.toml`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct PackageRef {
+    pub name: String,
+    pub version: String,
+}
+
+/// What `apply` last wrote to a target path.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct WriteRecord {
+    pub template: String,
+    /// The target as written in the template, before expansion
+    pub target: String,
+    /// SHA-256 of the rendered content
+    pub checksum: String,
+    /// Unix timestamp of the write
+    pub written_at: i64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct StoreData {
+    templates: HashMap<String, Template>,
+    provenance: HashMap<String, Provenance>,
+    synced_dirs: Vec<PathBuf>,
+    written: HashMap<PathBuf, WriteRecord>,
+    /// SHA-256 of the reload hook the user approved, by template name
+    approved_hooks: HashMap<String, String>,
+    /// Package repositories for `hyprink install`, in the order they were added
+    repositories: Vec<PathBuf>,
+}
+
+#[derive(Default)]
+pub struct Store {
+    path: PathBuf,
+    data: StoreData,
+}
+
+impl Store {
+    pub fn load(path: &Path) -> Result<Self> {
+        debug!("Loading store from {:?}", path);
+        let mut db = Store {
+            path: path.to_path_buf(),
+            ..Default::default()
+        };
+
+        if !path.exists() {
+            debug!("Store file does not exist, creating new");
+            return Ok(db);
+        }
+
+        let bytes = fs::read(path).context("Failed to open store database")?;
+        if bytes.is_empty() {
+            debug!("Store file is empty");
+            return Ok(db);
+        }
+
+        match migrate::decode(&bytes) {
+            Ok((version, data)) => {
+                debug!("Store schema version: {}", version);
+                db.data = data;
+                if version < migrate::SCHEMA_VERSION {
+                    let backup = path.with_extension(format!("db.v{}.bak", version));
+                    fs::write(&backup, &bytes)
+                        .context("Failed to back up store database before migration")?;
+                    logger::info(
+                        "STORE",
+                        &format!(
+                            "migrated store from schema v{} to v{} (backup: {})",
+                            version,
+                            migrate::SCHEMA_VERSION,
+                            backup.display()
+                        ),
+                    );
+                    db.save()?;
+                }
+            }
+            Err(e) if e.is_corruption() => {
+                logger::warn(
+                    "STORE",
+                    &format!("store database {} is corrupt: {}", path.display(), e),
+                );
+                db.data = db.recover(&bytes)?;
+            }
+            Err(e) => return Err(e).context("Failed to decode store database"),
+        }
+
+        debug!("Loaded {} templates", db.data.templates.len());
+        Ok(db)
+    }
+
+    /// Whether [`Store::load`] would write to `path`: to migrate an older
+    /// schema or to recover a corrupt file from a snapshot.
+    pub fn needs_repair(path: &Path) -> bool {
+        match fs::read(path) {
+            Ok(bytes) if !bytes.is_empty() => match migrate::schema_version(&bytes) {
+                Ok(version) => version < migrate::SCHEMA_VERSION,
+                Err(e) => e.is_corruption(),
+            },
+            _ => false,
+        }
+    }
+
+    /// Write the store via a synced temp file and rename, then snapshot it.
+    pub fn save(&self) -> Result<()> {
+        debug!("Saving store to {:?}", self.path);
+        let bytes = migrate::encode(&self.data)?;
+        write_atomic(&self.path, &bytes).context("Failed to write store database file")?;
+
+        if let Err(e) = self.snapshot(&bytes) {
+            logger::warn("STORE", &format!("failed to write store snapshot: {:#}", e));
+        }
+        Ok(())
+    }
+
+    /// Schema version and template count of a store file, or why it is unreadable.
+    pub fn verify_file(path: &Path) -> Result<StoreFileInfo> {
+        let bytes = fs::read(path).with_context(|| format!("Failed to read {:?}", path))?;
+        let (schema, data) = migrate::decode(&bytes)?;
+        Ok(StoreFileInfo {
+            schema,
+            templates: data.templates.len(),
+        })
+    }
+
+    /// Snapshots of the store at `path`, newest first.
+    pub fn snapshots(path: &Path) -> Vec<PathBuf> {
+        let prefix = snapshot_prefix(path);
+        let mut list: Vec<PathBuf> = fs::read_dir(snapshot_dir(path))
+            .into_iter()
+            .flatten()
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| {
+                p.file_name()
+                    .and_then(|n| n.to_str())
+                    .is_some_and(|n| n.starts_with(&prefix) && n.ends_with(".db"))
+            })
+            .collect();
+        list.sort();
+        list.reverse();
+        list
+    }
+
+    /// Replace the store at `path` with a validated snapshot.
+    pub fn restore_snapshot(path: &Path, snapshot: &Path) -> Result<StoreFileInfo> {
+        let bytes = fs::read(snapshot).with_context(|| format!("Failed to read {:?}", snapshot))?;
+        let (schema, data) = migrate::decode(&bytes)
+            .with_context(|| format!("Snapshot {:?} is not usable", snapshot))?;
+        write_atomic(path, &bytes).context("Failed to write store database file")?;
+        Ok(StoreFileInfo {
+            schema,
+            templates: data.templates.len(),
+        })
+    }
+
+    /// Load the newest valid snapshot, keeping the damaged file aside.
+    fn recover(&self, corrupt: &[u8]) -> Result<StoreData> {
+        for snapshot in Self::snapshots(&self.path) {
+            let Ok(bytes) = fs::read(&snapshot) else {
+                continue;
+            };
+            let Ok((_, data)) = migrate::decode(&bytes) else {
+                debug!("Snapshot {:?} is not valid either", snapshot);
+                continue;
+            };
+
+            let aside = self.path.with_extension("db.corrupt");
+            fs::write(&aside, corrupt).context("Failed to keep corrupt store database")?;
+            write_atomic(&self.path, &bytes).context("Failed to write store database file")?;
+            logger::warn(
+                "STORE",
+                &format!(
+                    "recovered store from snapshot {} (corrupt file kept as {})",
+                    snapshot.display(),
+                    aside.display()
+                ),
+            );
+            return Ok(data);
+        }
+
+        Err(anyhow!(
+            "Store database {:?} is corrupt and no valid snapshot was found",
+            self.path
+        ))
+    }
+
+    fn snapshot(&self, bytes: &[u8]) -> Result<()> {
+        let dir = snapshot_dir(&self.path);
+        fs::create_dir_all(&dir)?;
+        let millis = chrono::Utc::now().timestamp_millis();
+        let name = format!("{}{:013}.db", snapshot_prefix(&self.path), millis);
+        write_atomic(&dir.join(name), bytes)?;
+
+        for old in Self::snapshots(&self.path).iter().skip(KEEP_SNAPSHOTS) {
+            debug!("Pruning store snapshot {:?}", old);
+            let _ = fs::remove_file(old);
+        }
+        Ok(())
+    }
+
+    pub fn add(&mut self, template: Template) -> Result<()> {
+        debug!("Adding template: {}", template.manifest.name);
+        self.data
+            .templates
+            .insert(template.manifest.name.clone(), template);
+        Ok(())
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<Template> {
+        debug!("Removing template: {}", name);
+        self.data.provenance.remove(name);
+        self.data.written.retain(|_, w| w.template != name);
+        self.data.approved_hooks.remove(name);
+        self.data.templates.remove(name)
+    }
+
+    pub fn clear(&mut self) {
+        debug!("Clearing store (removing all templates)");
+        self.data.templates.clear();
+        self.data.provenance.clear();
+        self.data.written.clear();
+        self.data.approved_hooks.clear();
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Template> {
+        self.data.templates.get(name)
+    }
+
+    pub fn set_provenance(&mut self, name: &str, provenance: Provenance) {
+        debug!("Recording provenance for {}: {:?}", name, provenance.source);
+        self.data.provenance.insert(name.to_string(), provenance);
+    }
+
+    pub fn provenance(&self, name: &str) -> Option<&Provenance> {
+        self.data.provenance.get(name)
+    }
+
+    pub fn record_write(&mut self, path: &Path, record: WriteRecord) {
+        debug!("Recording write of {:?} by {}", path, record.template);
+        self.data.written.insert(path.to_path_buf(), record);
+    }
+
+    pub fn write_record(&self, path: &Path) -> Option<&WriteRecord> {
+        self.data.written.get(path)
+    }
+
+    /// Checksum of the last write to every recorded path.
+    pub fn written_checksums(&self) -> HashMap<PathBuf, String> {
+        self.data
+            .written
+            .iter()
+            .map(|(path, w)| (path.clone(), w.checksum.clone()))
+            .collect()
+    }
+
+    /// Remember that the user allowed the hook with digest `hook` to run for `name`.
+    pub fn approve_hook(&mut self, name: &str, hook: String) {
+        debug!("Approving hook of {}", name);
+        self.data.approved_hooks.insert(name.to_string(), hook);
+    }
+
+    /// Digest of the approved hook of every template that has one.
+    pub fn approved_hooks(&self) -> &HashMap<String, String> {
+        &self.data.approved_hooks
+    }
+
+    /// Names of templates whose recorded source sits directly in `dir`, as
+    /// `sync` scans it. Sources in subdirectories belong to someone else.
+    pub fn templates_from(&self, dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .data
+            .provenance
+            .iter()
+            .filter(|(_, p)| p.source.parent() == Some(dir))
+            .map(|(name, _)| name.clone())
+            .collect();
+        names.sort();
+        names
+    }
+
+    pub fn synced_dirs(&self) -> &[PathBuf] {
+        &self.data.synced_dirs
+    }
+
+    /// Remember `dir` for `hyprink sync`; returns false if already known.
+    pub fn add_synced_dir(&mut self, dir: &Path) -> bool {
+        if self.data.synced_dirs.iter().any(|d| d == dir) {
+            return false;
+        }
+        debug!("Remembering synced directory {:?}", dir);
+        self.data.synced_dirs.push(dir.to_path_buf());
+        true
+    }
+
+    pub fn remove_synced_dir(&mut self, dir: &Path) -> bool {
+        let before = self.data.synced_dirs.len();
+        self.data.synced_dirs.retain(|d| d != dir);
+        self.data.synced_dirs.len() != before
+    }
+
+    pub fn repositories(&self) -> &[PathBuf] {
+        &self.data.repositories
+    }
+
+    /// Remember a package repository; returns false if already known.
+    pub fn add_repository(&mut self, location: &Path) -> bool {
+        if self.data.repositories.iter().any(|r| r == location) {
+            return false;
+        }
+        debug!("Remembering repository {:?}", location);
+        self.data.repositories.push(location.to_path_buf());
+        true
+    }
+
+    pub fn remove_repository(&mut self, location: &Path) -> bool {
+        let before = self.data.repositories.len();
+        self.data.repositories.retain(|r| r != location);
+        self.data.repositories.len() != before
+    }
+
+    pub fn set_ignored(&mut self, name: &str, state: bool) -> Result<bool> {
+        if let Some(tpl) = self.data.templates.get_mut(name) {
+            tpl.manifest.ignored = state;
+            debug!("Set ignored status for {} to {}", name, state);
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
+    pub fn list(&self) -> Vec<&Template> {
+        let mut list: Vec<&Template> = self.data.templates.values().collect();
+        list.sort_by_key(|t| &t.manifest.name);
+        list
+    }
+
+    /// Templates in apply order (dependencies first, then by name).
+    pub fn apply_order(&self) -> Result<Vec<&Template>, ResolveError> {
+        resolver::apply_order(&self.list())
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.data.templates.contains_key(name)
+    }
+
+    /// Map each resolved target path to the names of the templates writing it.
+    pub fn target_owners(&self) -> BTreeMap<PathBuf, Vec<String>> {
+        owners(self.list().into_iter())
+    }
+
+    /// Conflicts between enabled templates, as checked before `apply`.
+    pub fn conflicts(&self) -> Vec<TargetConflict> {
+        let enabled = self.list().into_iter().filter(|t| !t.manifest.ignored);
+        unresolved(owners(enabled), |name| self.data.templates.get(name))
+    }
+
+    /// Conflicts `tpl` would introduce if added, replacing any stored template
+    /// of the same name.
+    pub fn conflicts_with(&self, tpl: &Template) -> Vec<TargetConflict> {
+        let others = self
+            .list()
+            .into_iter()
+            .filter(|t| t.manifest.name != tpl.manifest.name);
+        let map = owners(others.chain(std::iter::once(tpl)));
+        unresolved(map, |name| {
+            if name == tpl.manifest.name {
+                Some(tpl)
+            } else {
+                self.data.templates.get(name)
+            }
+        })
+        .into_iter()
+        .filter(|c| c.templates.contains(&tpl.manifest.name))
+        .collect()
+    }
+
+    pub fn iter(&self) -> std::collections::hash_map::Values<'_, String, Template> {
+        self.data.templates.values()
+    }
+}
+
+fn snapshot_dir(path: &Path) -> PathBuf {
+    path.parent()
+        .unwrap_or_else(|| Path::new("."))
+        .join("snapshots")
+}
+
+fn snapshot_prefix(path: &Path) -> String {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    format!("{}.", stem)
+}
+
+/// Replace `path` with `bytes` so readers see either the old or the new file,
+/// never a partial one.
+fn write_atomic(path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    let dir = path.parent().unwrap_or_else(|| Path::new("."));
+    fs::create_dir_all(dir)?;
+
+    let mut tmp_name = path.file_name().unwrap_or_default().to_os_string();
+    tmp_name.push(format!(".tmp.{}", std::process::id()));
+    let tmp = dir.join(tmp_name);
+
+    let result = (|| {
+        let mut file = File::create(&tmp)?;
+        file.write_all(bytes)?;
+        file.sync_all()?;
+        fs::rename(&tmp, path)
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    result?;
+
+    if let Ok(dir) = File::open(dir) {
+        let _ = dir.sync_all();
+    }
+    Ok(())
+}
+
+fn owners<'a>(templates: impl Iterator<Item = &'a Template>) -> BTreeMap<PathBuf, Vec<String>> {
+    let mut map: BTreeMap<PathBuf, Vec<String>> = BTreeMap::new();
+    for tpl in templates {
+        for target in tpl.target_paths() {
+            let names = map.entry(paths::canonical_target(target)).or_default();
+            if !names.contains(&tpl.manifest.name) {
+                names.push(tpl.manifest.name.clone());
+            }
+        }
+    }
+    map
+}
+
+/// Keep shared paths where no single owner lists every other owner in `overrides`.
+fn unresolved<'a>(
+    owners: BTreeMap<PathBuf, Vec<String>>,
+    lookup: impl Fn(&str) -> Option<&'a Template>,
+) -> Vec<TargetConflict> {
+    owners
+        .into_iter()
+        .filter(|(_, names)| names.len() > 1)
+        .filter(|(_, names)| {
+            !names.iter().any(|winner| {
+                lookup(winner).is_some_and(|w| {
+                    names
+                        .iter()
+                        .filter(|n| *n != winner)
+                        .all(|n| w.manifest.overrides.contains(n))
+                })
+            })
+        })
+        .map(|(path, mut templates)| {
+            templates.sort();
+            TargetConflict { path, templates }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::template::{Target, TemplateManifest};
+
+    fn tpl(name: &str, target: &str, overrides: &[&str]) -> Template {
+        Template {
+            manifest: TemplateManifest {
+                name: name.to_string(),
+                version: "0.1.0".to_string(),
+                authors: vec![],
+                description: String::new(),
+                repository: None,
+                license: None,
+                ignored: false,
+                depends_on: vec![],
+                after: vec![],
+                overrides: overrides.iter().map(|s| s.to_string()).collect(),
+            },
+            targets: vec![Target {
+                target: target.to_string(),
+                content: String::new(),
+            }],
+            files: vec![],
+            hooks: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_conflicts_with_detects_shared_target() {
+        let mut db = Store::default();
+        db.add(tpl(
+            "gtk-theme",
+            "/tmp/hyprink-test/gtk-3.0/settings.ini",
+            &[],
+        ))
+        .unwrap();
+
+        let clash = tpl(
+            "gtk-settings",
+            "/tmp/hyprink-test/gtk-3.0/./settings.ini",
+            &[],
+        );
+        let conflicts = db.conflicts_with(&clash);
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].templates, vec!["gtk-settings", "gtk-theme"]);
+
+        let winner = tpl(
+            "gtk-settings",
+            "/tmp/hyprink-test/gtk-3.0/settings.ini",
+            &["gtk-theme"],
+        );
+        assert!(db.conflicts_with(&winner).is_empty());
+    }
+
+    #[test]
+    fn test_templates_from_ignores_subdirectories() {
+        let mut db = Store::default();
+        for (name, source) in [
+            ("top", "/sync/top.tpl"),
+            ("nested", "/sync/nested/nested.pkg"),
+            ("elsewhere", "/other/elsewhere.tpl"),
+        ] {
+            db.add(tpl(name, &format!("/tmp/hyprink-test/{}", name), &[]))
+                .unwrap();
+            db.set_provenance(
+                name,
+                Provenance {
+                    source: PathBuf::from(source),
+                    entry: None,
+                    checksum: String::new(),
+                    added_at: 0,
+                    package: None,
+                },
+            );
+        }
+        assert_eq!(db.templates_from(Path::new("/sync")), vec!["top"]);
+        assert_eq!(db.templates_from(Path::new("/sync/nested")), vec!["nested"]);
+    }
+
+    #[test]
+    fn test_corrupt_store_recovers_from_snapshot() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("store.db");
+
+        let mut db = Store::load(&path).unwrap();
+        db.add(tpl("waybar", "/tmp/hyprink-test/waybar.css", &[]))
+            .unwrap();
+        db.save().unwrap();
+        assert_eq!(Store::snapshots(&path).len(), 1);
+
+        let mut bytes = fs::read(&path).unwrap();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0xff;
+        fs::write(&path, &bytes).unwrap();
+        assert!(Store::verify_file(&path).is_err());
+
+        let recovered = Store::load(&path).unwrap();
+        assert!(recovered.contains("waybar"));
+        assert!(Store::verify_file(&path).is_ok());
+        assert!(dir.path().join("store.db.corrupt").exists());
+    }
+}
@@ -0,0 +1,591 @@
+//! Typed font roles and the syntaxes different toolkits want them in.
+//!
+//! A role (`ui`, `mono`, ...) is a [`Font`]. Templates get every role as
+//! `font.<role>` and format it with a filter: `{{ font.ui | gtk_font }}`,
+//! `{{ font.mono | css_font(unit="px") }}` and so on. The filters also accept
+//! a plain family string, so `{{ fonts.ui | css_font }}` works too.
+
+use log::debug;
+use serde::de::{self, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::process::Command;
+use tera::{Tera, Value, from_value, to_value, try_get_value};
+
+/// Families every toolkit resolves itself; never quoted or looked up.
+const GENERIC_FAMILIES: &[&str] = &[
+    "serif",
+    "sans-serif",
+    "sans",
+    "monospace",
+    "cursive",
+    "fantasy",
+    "system-ui",
+    "ui-monospace",
+    "ui-sans-serif",
+    "ui-serif",
+    "emoji",
+    "math",
+];
+
+/// One font role.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Font {
+    pub family: String,
+    /// In points
+    #[serde(default)]
+    pub size: Option<f32>,
+    #[serde(default)]
+    pub weight: Option<FontWeight>,
+    #[serde(default)]
+    pub style: FontStyle,
+    /// Families to try when `family` lacks a glyph, in order
+    #[serde(default)]
+    pub fallbacks: Vec<String>,
+    /// OpenType feature tags such as `tnum` or `ss01`
+    #[serde(default)]
+    pub features: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FontStyle {
+    #[default]
+    Normal,
+    Italic,
+    Oblique,
+}
+
+/// A CSS-style weight from 1 to 1000. Configs may give a number or a name
+/// such as `semibold`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FontWeight(pub u16);
+
+/// Names for the hundreds, as `(weight, names)`; the first name is canonical.
+const WEIGHT_NAMES: &[(u16, &[&str])] = &[
+    (100, &["thin", "hairline"]),
+    (200, &["extralight", "ultralight"]),
+    (300, &["light"]),
+    (400, &["regular", "normal", "book"]),
+    (500, &["medium"]),
+    (600, &["semibold", "demibold"]),
+    (700, &["bold"]),
+    (800, &["extrabold", "ultrabold"]),
+    (900, &["black", "heavy"]),
+];
+
+impl FontWeight {
+    pub const REGULAR: Self = Self(400);
+
+    pub fn parse(value: &str) -> Option<Self> {
+        let normalized = value.trim().to_lowercase().replace(['-', '_', ' '], "");
+        if let Ok(n) = normalized.parse::<u16>() {
+            return (1..=1000).contains(&n).then_some(Self(n));
+        }
+        WEIGHT_NAMES
+            .iter()
+            .find(|(_, names)| names.contains(&normalized.as_str()))
+            .map(|(w, _)| Self(*w))
+    }
+
+    /// The named weight closest to this one.
+    fn nearest(self) -> u16 {
+        (self.0.clamp(100, 900) + 50) / 100 * 100
+    }
+
+    /// Lowercase name as fontconfig knows it.
+    fn name(self) -> &'static str {
+        let w = self.nearest();
+        WEIGHT_NAMES
+            .iter()
+            .find(|(n, _)| *n == w)
+            .map_or("regular", |(_, names)| names[0])
+    }
+
+    /// Style word in a Pango font description; `None` for regular.
+    fn pango_name(self) -> Option<&'static str> {
+        Some(match self.nearest() {
+            100 => "Thin",
+            200 => "Ultra-Light",
+            300 => "Light",
+            500 => "Medium",
+            600 => "Semi-Bold",
+            700 => "Bold",
+            800 => "Ultra-Bold",
+            900 => "Heavy",
+            _ => return None,
+        })
+    }
+
+    /// Qt 5's 0-99 weight scale.
+    fn qt5(self) -> u16 {
+        match self.nearest() {
+            100 => 0,
+            200 => 12,
+            300 => 25,
+            500 => 57,
+            600 => 63,
+            700 => 75,
+            800 => 81,
+            900 => 87,
+            _ => 50,
+        }
+    }
+}
+
+impl Serialize for FontWeight {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u16(self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for FontWeight {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct WeightVisitor;
+
+        impl Visitor<'_> for WeightVisitor {
+            type Value = FontWeight;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a weight from 1 to 1000 or a name like \"semibold\"")
+            }
+
+            fn visit_u64<E: de::Error>(self, v: u64) -> Result<FontWeight, E> {
+                u16::try_from(v)
+                    .ok()
+                    .filter(|n| (1..=1000).contains(n))
+                    .map(FontWeight)
+                    .ok_or_else(|| E::custom(format!("font weight {} is not in 1..=1000", v)))
+            }
+
+            fn visit_i64<E: de::Error>(self, v: i64) -> Result<FontWeight, E> {
+                let v = u64::try_from(v)
+                    .map_err(|_| E::custom(format!("font weight {} is not in 1..=1000", v)))?;
+                self.visit_u64(v)
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<FontWeight, E> {
+                FontWeight::parse(v)
+                    .ok_or_else(|| E::custom(format!("unknown font weight '{}'", v)))
+            }
+        }
+
+        // The config cache is bincode, which cannot tell numbers from names
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_any(WeightVisitor)
+        } else {
+            deserializer.deserialize_u16(WeightVisitor)
+        }
+    }
+}
+
+impl Font {
+    pub fn new(family: impl Into<String>) -> Self {
+        Self {
+            family: family.into(),
+            ..Default::default()
+        }
+    }
+
+    /// `family` followed by the fallbacks.
+    pub fn families(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.family.as_str()).chain(self.fallbacks.iter().map(String::as_str))
+    }
+
+    /// GTK `gtk-font-name` / gsettings: `Inter Semi-Bold Italic 11`.
+    pub fn gtk(&self) -> String {
+        self.pango_description(&self.family)
+    }
+
+    /// Pango font description with the fallbacks as a family list:
+    /// `Inter, Noto Sans Semi-Bold Italic 11`.
+    pub fn pango(&self) -> String {
+        self.pango_description(&self.families().collect::<Vec<_>>().join(", "))
+    }
+
+    fn pango_description(&self, families: &str) -> String {
+        let mut parts = vec![families.to_string()];
+        parts.extend(
+            self.weight
+                .and_then(FontWeight::pango_name)
+                .map(str::to_string),
+        );
+        match self.style {
+            FontStyle::Normal => {}
+            FontStyle::Italic => parts.push("Italic".to_string()),
+            FontStyle::Oblique => parts.push("Oblique".to_string()),
+        }
+        parts.extend(self.size.map(format_size));
+        parts.join(" ")
+    }
+
+    /// `QFont::toString()` as written to qt5ct/qt6ct configs. Without a size
+    /// the point size is `-1`, which Qt reads as unset.
+    pub fn qt(&self, version: u8) -> String {
+        let size = self.size.map_or_else(|| "-1".to_string(), format_size);
+        let weight = self.weight.unwrap_or(FontWeight::REGULAR);
+        let style = match self.style {
+            FontStyle::Normal => 0,
+            FontStyle::Italic => 1,
+            FontStyle::Oblique => 2,
+        };
+        if version >= 6 {
+            format!(
+                "{},{},-1,5,{},{},0,0,0,0,0,0,0,0,0,1",
+                self.family, size, weight.0, style
+            )
+        } else {
+            format!(
+                "{},{},-1,5,{},{},0,0,0,0",
+                self.family,
+                size,
+                weight.qt5(),
+                style
+            )
+        }
+    }
+
+    /// CSS `font` shorthand: `italic 600 11pt "Inter", sans-serif`. Without a
+    /// size only the family list is returned, for `font-family`.
+    pub fn css(&self, unit: &str) -> String {
+        let families = self
+            .families()
+            .map(|f| {
+                if is_generic(f) {
+                    f.to_string()
+                } else {
+                    format!("\"{}\"", f.replace('"', "\\\""))
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        let Some(size) = self.size else {
+            return families;
+        };
+        let mut parts = Vec::new();
+        match self.style {
+            FontStyle::Normal => {}
+            FontStyle::Italic => parts.push("italic".to_string()),
+            FontStyle::Oblique => parts.push("oblique".to_string()),
+        }
+        parts.extend(self.weight.map(|w| w.0.to_string()));
+        parts.push(format!("{}{}", format_size(size), unit));
+        parts.push(families);
+        parts.join(" ")
+    }
+
+    /// Fontconfig pattern:
+    /// `Inter,Noto Sans:size=11:weight=semibold:slant=italic:fontfeatures=tnum`.
+    pub fn fontconfig(&self) -> String {
+        let mut pattern = self
+            .families()
+            .map(escape_fontconfig)
+            .collect::<Vec<_>>()
+            .join(",");
+        if let Some(size) = self.size {
+            pattern.push_str(&format!(":size={}", format_size(size)));
+        }
+        if let Some(weight) = self.weight {
+            pattern.push_str(&format!(":weight={}", weight.name()));
+        }
+        match self.style {
+            FontStyle::Normal => {}
+            FontStyle::Italic => pattern.push_str(":slant=italic"),
+            FontStyle::Oblique => pattern.push_str(":slant=oblique"),
+        }
+        if !self.features.is_empty() {
+            pattern.push_str(&format!(":fontfeatures={}", self.features.join(",")));
+        }
+        pattern
+    }
+}
+
+/// Prefix of the `fonts` keys that hold a role's size, as in `size_ui`.
+pub const SIZE_PREFIX: &str = "size_";
+
+/// Every font role: the typed `[theme.font.<role>]` tables, plus a role for
+/// each `<role>` string in `fonts` (sized by `size_<role>`) that has no table.
+pub fn roles(
+    strings: &HashMap<String, String>,
+    typed: &HashMap<String, Font>,
+) -> BTreeMap<String, Font> {
+    let mut roles: BTreeMap<String, Font> = strings
+        .iter()
+        .filter(|(key, _)| !key.starts_with(SIZE_PREFIX))
+        .map(|(role, family)| {
+            let mut font = Font::new(family.as_str());
+            font.size = strings
+                .get(&format!("{}{}", SIZE_PREFIX, role))
+                .and_then(|s| parse_size(s));
+            (role.clone(), font)
+        })
+        .collect();
+    roles.extend(
+        typed
+            .iter()
+            .map(|(role, font)| (role.clone(), font.clone())),
+    );
+    roles
+}
+
+/// The `fonts` strings templates have always seen, with `<role>` and
+/// `size_<role>` filled in for typed roles so older templates keep working.
+pub fn strings(
+    strings: &HashMap<String, String>,
+    typed: &HashMap<String, Font>,
+) -> BTreeMap<String, String> {
+    let mut out: BTreeMap<String, String> = strings
+        .iter()
+        .map(|(k, v)| (k.clone(), v.clone()))
+        .collect();
+    for (role, font) in typed {
+        out.insert(role.clone(), font.family.clone());
+        if let Some(size) = font.size {
+            out.insert(format!("{}{}", SIZE_PREFIX, role), format_size(size));
+        }
+    }
+    out
+}
+
+/// A `size_<role>` string as a size in points: `11`, `10.5` or `10pt`.
+pub fn parse_size(value: &str) -> Option<f32> {
+    let value = value.trim();
+    value
+        .strip_suffix("pt")
+        .unwrap_or(value)
+        .trim_end()
+        .parse::<f32>()
+        .ok()
+        .filter(|s| s.is_finite() && *s > 0.0)
+}
+
+pub fn is_generic(family: &str) -> bool {
+    GENERIC_FAMILIES.contains(&family.to_lowercase().as_str())
+}
+
+/// Font families fontconfig knows, lowercased, or `None` if `fc-list` is not
+/// available.
+pub fn installed_families() -> Option<HashSet<String>> {
+    let output = Command::new("fc-list")
+        .args([":", "family"])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        debug!("fc-list exited with {}", output.status);
+        return None;
+    }
+    // One font per line; localized names of the same family are comma separated
+    Some(
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .flat_map(|line| line.split(','))
+            .map(|name| name.replace('\\', "").trim().to_lowercase())
+            .filter(|name| !name.is_empty())
+            .collect(),
+    )
+}
+
+/// Register the font filters on `tera`.
+pub fn register_filters(tera: &mut Tera) {
+    tera.register_filter("gtk_font", gtk_font);
+    tera.register_filter("pango_font", pango_font);
+    tera.register_filter("qt_font", qt_font);
+    tera.register_filter("css_font", css_font);
+    tera.register_filter("fontconfig", fontconfig);
+}
+
+/// Sizes are printed without a fraction when they have none: `11`, `10.5`.
+fn format_size(size: f32) -> String {
+    if size.fract() == 0.0 {
+        format!("{}", size as i64)
+    } else {
+        format!("{}", size)
+    }
+}
+
+fn escape_fontconfig(family: &str) -> String {
+    let mut out = String::with_capacity(family.len());
+    for c in family.chars() {
+        if matches!(c, '\\' | '-' | ':' | ',') {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// Filter input: a role from `font`, or a bare family string from `fonts`.
+fn filter_font(filter: &str, value: &Value) -> tera::Result<Font> {
+    match value {
+        Value::String(family) => Ok(Font::new(family.as_str())),
+        _ => from_value(value.clone()).map_err(|e| {
+            tera::Error::msg(format!(
+                "Filter `{}` expects a font role or a family name: {}",
+                filter, e
+            ))
+        }),
+    }
+}
+
+/// Tera filter: gtk_font
+fn gtk_font(value: &Value, _args: &HashMap<String, Value>) -> tera::Result<Value> {
+    Ok(to_value(filter_font("gtk_font", value)?.gtk())?)
+}
+
+/// Tera filter: pango_font
+fn pango_font(value: &Value, _args: &HashMap<String, Value>) -> tera::Result<Value> {
+    Ok(to_value(filter_font("pango_font", value)?.pango())?)
+}
+
+/// Tera filter: qt_font(version=5)
+fn qt_font(value: &Value, args: &HashMap<String, Value>) -> tera::Result<Value> {
+    let font = filter_font("qt_font", value)?;
+    let version = match args.get("version") {
+        Some(v) => try_get_value!("qt_font", "version", u8, v),
+        None => 5,
+    };
+    Ok(to_value(font.qt(version))?)
+}
+
+/// Tera filter: css_font(unit="pt")
+fn css_font(value: &Value, args: &HashMap<String, Value>) -> tera::Result<Value> {
+    let unit = match args.get("unit") {
+        Some(v) => try_get_value!("css_font", "unit", String, v),
+        None => "pt".to_string(),
+    };
+    Ok(to_value(filter_font("css_font", value)?.css(&unit))?)
+}
+
+/// Tera filter: fontconfig
+fn fontconfig(value: &Value, _args: &HashMap<String, Value>) -> tera::Result<Value> {
+    Ok(to_value(filter_font("fontconfig", value)?.fontconfig())?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{Config, test_config};
+    use crate::processor;
+    use crate::template::Template;
+
+    #[test]
+    fn test_font_roles_format_per_toolkit() {
+        let typed: HashMap<String, Font> = toml::from_str(
+            r#"
+ui = { family = "Inter", size = 11, weight = "semi-bold", style = "italic", fallbacks = ["Noto Sans", "sans-serif"], features = ["tnum", "ss01"] }
+"#,
+        )
+        .unwrap();
+        let legacy: HashMap<String, String> = [("mono", "Fira Code"), ("size_mono", "10.5")]
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+
+        let roles = roles(&legacy, &typed);
+        let ui = &roles["ui"];
+        assert_eq!(ui.weight, Some(FontWeight(600)));
+        assert_eq!(ui.gtk(), "Inter Semi-Bold Italic 11");
+        assert_eq!(
+            ui.pango(),
+            "Inter, Noto Sans, sans-serif Semi-Bold Italic 11"
+        );
+        assert_eq!(ui.qt(5), "Inter,11,-1,5,63,1,0,0,0,0");
+        assert_eq!(ui.qt(6), "Inter,11,-1,5,600,1,0,0,0,0,0,0,0,0,0,1");
+        assert_eq!(
+            ui.css("px"),
+            r#"italic 600 11px "Inter", "Noto Sans", sans-serif"#
+        );
+        assert_eq!(
+            ui.fontconfig(),
+            r"Inter,Noto Sans,sans\-serif:size=11:weight=semibold:slant=italic:fontfeatures=tnum,ss01"
+        );
+
+        let mono = &roles["mono"];
+        assert_eq!(mono.size, Some(10.5));
+        assert_eq!(mono.css("pt"), r#"10.5pt "Fira Code""#);
+        assert_eq!(mono.qt(5), "Fira Code,10.5,-1,5,50,0,0,0,0,0");
+
+        let strings = strings(&legacy, &typed);
+        assert_eq!(strings["ui"], "Inter");
+        assert_eq!(strings["size_ui"], "11");
+        assert_eq!(strings["size_mono"], "10.5");
+
+        assert_eq!(FontWeight::parse("650").map(|w| w.name()), Some("bold"));
+        assert!(toml::from_str::<Font>("family = \"x\"\nweight = 1200").is_err());
+        assert!(toml::from_str::<Font>("family = \"x\"\nweight = \"chunky\"").is_err());
+
+        let mut tera = Tera::default();
+        register_filters(&mut tera);
+        let mut ctx = tera::Context::new();
+        ctx.insert("font", &roles);
+        ctx.insert("fonts", &strings);
+        let out = tera
+            .render_str(
+                "{{ font.mono | gtk_font }}|{{ fonts.ui | css_font }}|{{ font.ui | qt_font(version=6) }}",
+                &ctx,
+            )
+            .unwrap();
+        assert_eq!(
+            out,
+            "Fira Code 10.5|\"Inter\"|Inter,11,-1,5,600,1,0,0,0,0,0,0,0,0,0,1"
+        );
+        assert_eq!(
+            tera.render_str("{{ fonts.ui | qt_font }}", &ctx).unwrap(),
+            "Inter,-1,-1,5,50,0,0,0,0,0"
+        );
+    }
+
+    #[test]
+    fn test_bundled_qt_template_renders_plain_fonts() {
+        let template: Template =
+            toml::from_str(include_str!("../assets/templates/qt-theme.tpl")).unwrap();
+        let mut config = test_config();
+        for color in [
+            "bg",
+            "fg",
+            "black",
+            "bright_black",
+            "error",
+            "primary",
+            "selection_bg",
+            "selection_fg",
+        ] {
+            config
+                .theme
+                .colors
+                .insert(color.to_string(), "#282a36".to_string());
+        }
+        config
+            .theme
+            .fonts
+            .insert("ui".to_string(), "Noto Sans".to_string());
+
+        let render = |config: &Config| {
+            let (mut tera, ctx) = processor::render_context(config);
+            template
+                .outputs()
+                .map(|t| tera.render_str(&t.content, &ctx).unwrap())
+                .collect::<String>()
+        };
+
+        let out = render(&config);
+        assert!(
+            out.contains("general=\"Noto Sans,-1,-1,5,50,0,0,0,0,0\""),
+            "{}",
+            out
+        );
+
+        config
+            .theme
+            .fonts
+            .insert("size_ui".to_string(), "10pt".to_string());
+        let out = render(&config);
+        assert!(
+            out.contains("general=\"Noto Sans,10,-1,5,50,0,0,0,0,0\""),
+            "{}",
+            out
+        );
+        assert!(out.contains("general=\"Noto Sans,10,-1,5,400,0,0,0,0,0,0,0,0,0,0,1\""));
+    }
+}
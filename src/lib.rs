@@ -1,10 +1,21 @@
 pub mod config;
 pub mod db;
+pub mod digest;
 pub mod factory;
+pub mod fonts;
+pub mod lint;
 pub mod logger;
 pub mod packager;
+pub mod paths;
 pub mod processor;
+pub mod repository;
+pub mod resolver;
+pub mod signing;
+pub mod status;
 pub mod template;
+pub mod themes;
+#[cfg(target_os = "linux")]
+pub mod watch;
 
 #[cfg(feature = "cli")]
 pub mod cli;
@@ -0,0 +1,412 @@
+//! Static checks for templates and the config they are rendered with.
+//!
+//! Nothing here writes to disk: templates are rendered in memory and target
+//! paths are only resolved against the [`PathPolicy`].
+
+use crate::config::{self, Config};
+use crate::fonts;
+use crate::paths::PathPolicy;
+use crate::processor;
+use crate::template::{self, Template};
+use serde::Serialize;
+use std::collections::{BTreeSet, HashSet};
+use std::error::Error;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+/// A single finding.
+#[derive(Debug, Clone, Serialize)]
+pub struct Issue {
+    pub severity: Severity,
+    /// Target path or config key the issue refers to, if any.
+    pub location: Option<String>,
+    pub message: String,
+}
+
+impl Issue {
+    fn error(location: Option<&str>, message: String) -> Self {
+        Self {
+            severity: Severity::Error,
+            location: location.map(str::to_string),
+            message,
+        }
+    }
+
+    fn warning(location: Option<&str>, message: String) -> Self {
+        Self {
+            severity: Severity::Warning,
+            location: location.map(str::to_string),
+            message,
+        }
+    }
+}
+
+pub fn has_errors(issues: &[Issue]) -> bool {
+    issues.iter().any(|i| i.severity == Severity::Error)
+}
+
+/// Render every target of `tpl` against `config` and check where it would be written.
+pub fn lint_template(tpl: &Template, config: &Config, policy: &PathPolicy) -> Vec<Issue> {
+    let mut issues = Vec::new();
+
+    if tpl.manifest.name.trim().is_empty() {
+        issues.push(Issue::error(None, "manifest name is empty".to_string()));
+    }
+    if template::parse_version(&tpl.manifest.version).is_none() {
+        issues.push(Issue::warning(
+            None,
+            format!(
+                "version '{}' is not semver; updates compare it as plain text",
+                tpl.manifest.version
+            ),
+        ));
+    }
+    if tpl.targets.is_empty() && tpl.files.is_empty() {
+        issues.push(Issue::warning(None, "template writes no files".to_string()));
+    }
+
+    let (mut tera, ctx) = processor::render_context(config);
+    let icons = config.icons.resolve(&config.theme.active_icons);
+    let mut seen = HashSet::new();
+
+    for target in tpl.outputs() {
+        let location = Some(target.target.as_str());
+
+        match policy.resolve(&target.target) {
+            Ok(path) => {
+                if !seen.insert(path.clone()) {
+                    issues.push(Issue::error(
+                        location,
+                        format!("{} is written more than once", path.display()),
+                    ));
+                }
+            }
+            Err(e) => issues.push(Issue::error(location, e.to_string())),
+        }
+
+        if let Err(e) = tera.render_str(&target.content, &ctx) {
+            issues.push(Issue::error(location, describe(&e)));
+        }
+
+        for icon in icon_references(&target.content) {
+            if !config.icons.defines(icon) {
+                issues.push(Issue::warning(
+                    location,
+                    format!("icon '{}' is not defined in any icon set", icon),
+                ));
+            } else if !icons.contains_key(icon) {
+                issues.push(Issue::warning(
+                    location,
+                    format!(
+                        "icon '{}' is not in '{}' or its fallbacks",
+                        icon, config.theme.active_icons
+                    ),
+                ));
+            }
+        }
+    }
+
+    issues
+}
+
+/// Names used as `icons.<name>` in template content, each once.
+fn icon_references(content: &str) -> BTreeSet<&str> {
+    let mut found = BTreeSet::new();
+    for (start, _) in content.match_indices("icons.") {
+        let preceded_by_ident = content[..start]
+            .chars()
+            .next_back()
+            .is_some_and(|c| c.is_alphanumeric() || c == '_' || c == '.');
+        if preceded_by_ident {
+            continue;
+        }
+        let rest = &content[start + "icons.".len()..];
+        let end = rest
+            .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .unwrap_or(rest.len());
+        if end > 0 {
+            found.insert(&rest[..end]);
+        }
+    }
+    found
+}
+
+/// Check config values that parse fine but will not render the way they look.
+pub fn check_config(config: &Config) -> Vec<Issue> {
+    let mut issues = Vec::new();
+
+    let icons = config.theme.active_icons.as_str();
+    if !config.icons.sets.contains_key(icons) {
+        issues.push(Issue::warning(
+            Some("theme.active_icons"),
+            format!(
+                "unknown icon set '{}', {} icons will be used",
+                icons,
+                config::DEFAULT_ICON_SET
+            ),
+        ));
+    }
+
+    let mut sets: Vec<_> = config.icons.sets.keys().collect();
+    sets.sort();
+    for set in sets {
+        let Some(fallback) = config.icons.fallback(set) else {
+            continue;
+        };
+        let location = format!("icons.{}.{}", set, config::ICON_FALLBACK_KEY);
+        if !config.icons.sets.contains_key(fallback) {
+            issues.push(Issue::warning(
+                Some(&location),
+                format!("unknown icon set '{}'", fallback),
+            ));
+        } else if config.icons.chain(fallback).contains(&set.as_str()) {
+            issues.push(Issue::warning(
+                Some(&location),
+                format!("fallback chain of '{}' loops back to it", set),
+            ));
+        }
+    }
+
+    let mut colors: Vec<_> = config.theme.colors.iter().collect();
+    colors.sort();
+    for (key, value) in colors {
+        if !is_hex_color(value) {
+            issues.push(Issue::warning(
+                Some(&format!("theme.colors.{}", key)),
+                format!("'{}' is not a #rrggbb color", value),
+            ));
+        }
+    }
+
+    issues.extend(check_fonts(config));
+
+    for root in &config.paths.allowed_roots {
+        let path = crate::paths::expand_target(root);
+        if !path.is_dir() {
+            issues.push(Issue::warning(
+                Some("paths.allowed_roots"),
+                format!("{} does not exist", path.display()),
+            ));
+        }
+    }
+
+    issues
+}
+
+/// Font sizes must be positive numbers, and families (where `fc-list` is
+/// available to ask) must be installed.
+fn check_fonts(config: &Config) -> Vec<Issue> {
+    let mut issues = Vec::new();
+
+    let mut sizes: Vec<_> = config
+        .theme
+        .fonts
+        .iter()
+        .filter(|(key, _)| key.starts_with(fonts::SIZE_PREFIX))
+        .collect();
+    sizes.sort();
+    for (key, value) in sizes {
+        if fonts::parse_size(value).is_none() {
+            issues.push(Issue::warning(
+                Some(&format!("theme.fonts.{}", key)),
+                format!("'{}' is not a size in points", value),
+            ));
+        }
+    }
+
+    let roles = config.theme.font_roles();
+    let installed = if roles.is_empty() {
+        None
+    } else {
+        fonts::installed_families()
+    };
+    for (role, font) in &roles {
+        let location = if config.theme.font.contains_key(role) {
+            format!("theme.font.{}", role)
+        } else {
+            format!("theme.fonts.{}", role)
+        };
+        if font.family.trim().is_empty() {
+            issues.push(Issue::warning(
+                Some(&location),
+                "no font family".to_string(),
+            ));
+            continue;
+        }
+        if font.size.is_some_and(|s| !s.is_finite() || s <= 0.0) {
+            issues.push(Issue::warning(
+                Some(&location),
+                "font size must be a positive number".to_string(),
+            ));
+        }
+        let Some(installed) = &installed else {
+            continue;
+        };
+        for family in font.families().filter(|f| !fonts::is_generic(f)) {
+            if !installed.contains(&family.to_lowercase()) {
+                issues.push(Issue::warning(
+                    Some(&location),
+                    format!("font family '{}' is not installed", family),
+                ));
+            }
+        }
+    }
+
+    issues
+}
+
+/// Check the config file at `path` from scratch, bypassing the binary cache.
+pub fn check_config_file(path: &Path) -> Vec<Issue> {
+    match Config::load_from_path(path) {
+        Ok(config) => check_config(&config),
+        Err(e) => vec![Issue::error(None, e.to_string())],
+    }
+}
+
+fn is_hex_color(value: &str) -> bool {
+    let Some(hex) = value.strip_prefix('#') else {
+        return false;
+    };
+    matches!(hex.len(), 6 | 8) && hex.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Tera keeps the useful part of its messages in the source chain and names
+/// inline templates after an internal placeholder, which is dropped here.
+pub(crate) fn describe(err: &dyn Error) -> String {
+    const ONE_OFF: &str = "'__tera_one_off'";
+    let mut parts = Vec::new();
+    let mut current = Some(err);
+    while let Some(e) = current {
+        let msg = e.to_string();
+        if !msg.starts_with(&format!("Failed to render {}", ONE_OFF)) {
+            parts.push(msg.replace(&format!(" while rendering {}", ONE_OFF), ""));
+        }
+        current = e.source();
+    }
+    parts.join(": ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::template::{Target, TemplateManifest};
+
+    #[test]
+    fn test_lint_reports_render_and_path_errors() {
+        let config = crate::config::test_config();
+        let tpl = Template {
+            manifest: TemplateManifest {
+                name: "demo".to_string(),
+                version: "1.0.0".to_string(),
+                authors: vec![],
+                description: String::new(),
+                repository: None,
+                license: None,
+                ignored: false,
+                depends_on: vec![],
+                after: vec![],
+                overrides: vec![],
+            },
+            targets: vec![
+                Target {
+                    target: "~/.config/demo/ok.conf".to_string(),
+                    content: "bg = {{ colors.bg }}".to_string(),
+                },
+                Target {
+                    target: "~/.config/demo/broken.conf".to_string(),
+                    content: "accent = {{ colors.accent }}".to_string(),
+                },
+                Target {
+                    target: "/etc/demo.conf".to_string(),
+                    content: String::new(),
+                },
+            ],
+            files: vec![],
+            hooks: Default::default(),
+        };
+
+        let issues = lint_template(&tpl, &config, &PathPolicy::default());
+        let locations: Vec<_> = issues
+            .iter()
+            .filter_map(|i| i.location.as_deref())
+            .collect();
+        assert_eq!(
+            locations,
+            vec!["~/.config/demo/broken.conf", "/etc/demo.conf"]
+        );
+        assert!(issues[0].message.contains("colors.accent"));
+        assert!(has_errors(&issues));
+
+        let warnings: Vec<_> = check_config(&config)
+            .into_iter()
+            .filter_map(|i| i.location)
+            .collect();
+        assert_eq!(warnings, vec!["theme.active_icons", "theme.colors.fg"]);
+    }
+
+    #[test]
+    fn test_icon_sets_fall_back_and_missing_icons_warn() {
+        let mut config = crate::config::test_config();
+        config.icons = toml::from_str(
+            r##"
+emoji = { fallback = "nerdfont", ok = "+1" }
+nerdfont = { fallback = "ascii", ok = "nf-ok", warn = "nf-warn" }
+ascii = { ok = "*", warn = "!", net = "#" }
+loop_a = { fallback = "loop_b" }
+loop_b = { fallback = "loop_a" }
+"##,
+        )
+        .unwrap();
+
+        let icons = config.icons.resolve("emoji");
+        assert_eq!(icons["ok"], "+1");
+        assert_eq!(icons["warn"], "nf-warn");
+        assert_eq!(icons["net"], "#");
+        assert!(!icons.contains_key(config::ICON_FALLBACK_KEY));
+        assert_eq!(config.icons.chain("missing"), vec!["ascii"]);
+
+        let warnings: Vec<_> = check_config(&config)
+            .into_iter()
+            .filter_map(|i| i.location)
+            .collect();
+        assert_eq!(
+            warnings,
+            vec![
+                "icons.loop_a.fallback",
+                "icons.loop_b.fallback",
+                "theme.colors.fg"
+            ]
+        );
+
+        let tpl: Template = toml::from_str(
+            r#"
+[manifest]
+name = "bar"
+version = "1.0.0"
+authors = []
+description = ""
+
+[[targets]]
+target = "~/.config/bar/icons"
+content = "{{ icons.ok }} {{ icons.battery | default(value='') }} {{ myicons.x }}"
+"#,
+        )
+        .unwrap();
+        config.theme.active_icons = "emoji".to_string();
+        let messages: Vec<_> = lint_template(&tpl, &config, &PathPolicy::default())
+            .into_iter()
+            .filter(|i| i.severity == Severity::Warning)
+            .map(|i| i.message)
+            .collect();
+        assert_eq!(
+            messages,
+            vec!["icon 'battery' is not defined in any icon set"]
+        );
+    }
+}
@@ -1,9 +1,20 @@
 use std::sync::OnceLock;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 // Re-export types from hyprlog
 pub use hyprlog::{CleanupOptions, CleanupResult, Level, LogStats};
 
 static LOGGER: OnceLock<hyprlog::Logger> = OnceLock::new();
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+/// Drop messages below `Warn`, e.g. while stdout carries machine-readable output.
+pub fn set_quiet(quiet: bool) {
+    QUIET.store(quiet, Ordering::Relaxed);
+}
+
+fn quiet() -> bool {
+    QUIET.load(Ordering::Relaxed)
+}
 
 /// Get the global logger instance, initialized with default settings for hyprink.
 fn get_logger() -> &'static hyprlog::Logger {
@@ -25,16 +36,25 @@ fn get_logger() -> &'static hyprlog::Logger {
 
 /// Log a message with the given level and scope.
 pub fn log(level: Level, scope: &str, msg: &str) {
+    if quiet() && matches!(level, Level::Trace | Level::Debug | Level::Info) {
+        return;
+    }
     get_logger().log(level, scope, msg);
 }
 
 /// Log an info message.
 pub fn info(scope: &str, msg: &str) {
+    if quiet() {
+        return;
+    }
     get_logger().info(scope, msg);
 }
 
 /// Log a debug message.
 pub fn debug(scope: &str, msg: &str) {
+    if quiet() {
+        return;
+    }
     get_logger().debug(scope, msg);
 }
 
@@ -50,5 +70,8 @@ pub fn error(scope: &str, msg: &str) {
 
 /// Log a trace message.
 pub fn trace(scope: &str, msg: &str) {
+    if quiet() {
+        return;
+    }
     get_logger().trace(scope, msg);
 }
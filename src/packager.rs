@@ -1,55 +1,642 @@
-use anyhow::{Context, Result};
+use crate::config::ThemeFile;
+use crate::digest;
+use crate::template::{self, Template};
+use crate::themes;
+use anyhow::{Context, Result, anyhow, bail};
+use globset::{GlobBuilder, GlobMatcher};
 use log::debug;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fs::{self, File};
-use std::io::{Read, Write};
-use std::path::Path;
+use std::io::{Read, Seek, Write};
+use std::path::{Component, Path, PathBuf};
+use thiserror::Error;
 use zip::write::SimpleFileOptions;
-use zip::{ZipArchive, ZipWriter};
+use zip::{CompressionMethod, DateTime, ZipArchive, ZipWriter};
 
-/// Pack all .tpl files from a source directory into a .pkg archive.
-pub fn pack(source_dir: &Path, output_file: &Path) -> Result<()> {
+/// Most entries a package may hold.
+pub const MAX_ENTRIES: usize = 1024;
+/// Most bytes a package may expand to.
+pub const MAX_UNPACKED_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Why an archive was refused before anything was extracted from it.
+#[derive(Error, Debug)]
+pub enum UnpackError {
+    #[error("package contains unsafe entries: {}", .0.join(", "))]
+    UnsafeEntries(Vec<String>),
+    #[error("package has {count} entries, more than the limit of {}", MAX_ENTRIES)]
+    TooManyEntries { count: usize },
+    #[error("package expands to more than {} bytes", MAX_UNPACKED_SIZE)]
+    TooLarge,
+}
+
+/// Name of the manifest at the root of a .pkg archive.
+pub const MANIFEST: &str = "package.toml";
+/// Directory inside a package whose `<name>.toml` files are themes.
+pub const THEMES_DIR: &str = "themes";
+/// Gitignore-style patterns in a source directory that `pack` leaves out.
+pub const IGNORE_FILE: &str = ".hyprinkignore";
+
+/// Never packed, whatever `.hyprinkignore` says.
+const ALWAYS_IGNORED: &[&str] = &[".git/", IGNORE_FILE, "*.pkg", "*.pkg.sig"];
+
+/// How entries are compressed. Every method produces the same bytes for the
+/// same input, so packages stay reproducible either way.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum Compression {
+    Stored,
+    #[default]
+    Deflate,
+    Bzip2,
+    Zstd,
+}
+
+impl From<Compression> for CompressionMethod {
+    fn from(c: Compression) -> Self {
+        match c {
+            Compression::Stored => CompressionMethod::Stored,
+            Compression::Deflate => CompressionMethod::Deflated,
+            Compression::Bzip2 => CompressionMethod::Bzip2,
+            Compression::Zstd => CompressionMethod::Zstd,
+        }
+    }
+}
+
+/// What [`pack_with`] picks up from the source directory and how it writes it.
+#[derive(Debug, Clone, Default)]
+pub struct PackOptions {
+    pub compression: Compression,
+    /// Only files matching one of these globs are packed (all if empty).
+    pub include: Vec<String>,
+    /// Extra patterns to leave out, in `.hyprinkignore` syntax.
+    pub exclude: Vec<String>,
+}
+
+/// `package.toml`: what a package is and exactly which files it ships.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PackageManifest {
+    pub package: PackageInfo,
+    #[serde(default)]
+    pub contents: Vec<PackageEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PackageInfo {
+    pub name: String,
+    pub version: String,
+    #[serde(default)]
+    pub authors: Vec<String>,
+    #[serde(default)]
+    pub description: String,
+    pub license: Option<String>,
+    pub repository: Option<String>,
+    /// Oldest hyprink release able to install the package.
+    pub min_hyprink: Option<String>,
+}
+
+impl PackageInfo {
+    pub fn new(name: &str, version: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            version: version.to_string(),
+            authors: vec![],
+            description: String::new(),
+            license: None,
+            repository: None,
+            min_hyprink: None,
+        }
+    }
+}
+
+/// One file inside the archive.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PackageEntry {
+    pub path: String,
+    /// Manifest name of the template in that file; `None` for other assets
+    pub template: Option<String>,
+    pub sha256: String,
+}
+
+/// A .pkg archive read and checked against its manifest.
+#[derive(Debug)]
+pub struct Package {
+    /// `None` for packages built before `package.toml` existed; their
+    /// contents cannot be verified.
+    pub manifest: Option<PackageManifest>,
+    pub templates: Vec<(String, Template)>,
+    /// `(theme name, content)` of every `themes/<name>.toml`
+    pub themes: Vec<(String, String)>,
+}
+
+/// What a source directory may say about itself in its own `package.toml`.
+/// The `contents` are always generated by `pack`.
+#[derive(Deserialize)]
+struct PackageSource {
+    package: PackageInfo,
+}
+
+/// [`pack_with`] using the default options.
+pub fn pack(source_dir: &Path, output_file: &Path) -> Result<PackageManifest> {
+    pack_with(source_dir, output_file, &PackOptions::default())
+}
+
+/// Pack a source directory, subdirectories and assets included, into a .pkg
+/// archive. Metadata comes from `package.toml` in the directory if present,
+/// otherwise the directory name is used at version 0.1.0. The same input
+/// always produces a byte-identical archive.
+pub fn pack_with(
+    source_dir: &Path,
+    output_file: &Path,
+    opts: &PackOptions,
+) -> Result<PackageManifest> {
     debug!("Packing {:?} into {:?}", source_dir, output_file);
-    let file = File::create(output_file).context("Failed to create output file")?;
-    let mut zip = ZipWriter::new(file);
-    let options = SimpleFileOptions::default();
+    let mut filter = Filter::default();
+    let ignore_file = source_dir.join(IGNORE_FILE);
+    if ignore_file.exists() {
+        for line in fs::read_to_string(&ignore_file)?.lines() {
+            filter.exclude(line)?;
+        }
+    }
+    for pattern in ALWAYS_IGNORED
+        .iter()
+        .copied()
+        .chain(opts.exclude.iter().map(String::as_str))
+    {
+        filter.exclude(pattern)?;
+    }
+    for pattern in &opts.include {
+        filter.include(pattern)?;
+    }
+
+    let mut entries = Vec::new();
+    collect(source_dir, source_dir, &filter, &mut entries)?;
+    entries.sort();
+
+    let info_path = source_dir.join(MANIFEST);
+    let info = if info_path.exists() {
+        let text = fs::read_to_string(&info_path)?;
+        toml::from_str::<PackageSource>(&text)
+            .with_context(|| format!("Failed to parse {:?}", info_path))?
+            .package
+    } else {
+        let dir = fs::canonicalize(source_dir)?;
+        let name = dir.file_name().unwrap_or_default().to_string_lossy();
+        PackageInfo::new(&name, "0.1.0")
+    };
+
+    let manifest = manifest_for(info, &entries)?;
+    write_package_with(output_file, &manifest, &entries, opts.compression)?;
+    Ok(manifest)
+}
 
-    for entry in fs::read_dir(source_dir)? {
+/// Add every file below `dir` that passes `filter`, named relative to `root`
+/// with `/` separators. Symlinks are not followed.
+fn collect(
+    root: &Path,
+    dir: &Path,
+    filter: &Filter,
+    entries: &mut Vec<(String, Vec<u8>)>,
+) -> Result<()> {
+    for entry in fs::read_dir(dir)? {
         let entry = entry?;
         let path = entry.path();
-        if path.extension().is_some_and(|ext| ext == "tpl") {
-            let filename = path.file_name().unwrap().to_string_lossy();
-            debug!("Adding file: {}", filename);
-            zip.start_file(filename.as_ref(), options)?;
-            let content = fs::read_to_string(&path)?;
-            zip.write_all(content.as_bytes())?;
+        let file_type = entry.file_type()?;
+        let rel = path
+            .strip_prefix(root)
+            .expect("walked below root")
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+
+        if file_type.is_dir() {
+            if !filter.is_excluded(&rel, true) {
+                collect(root, &path, filter, entries)?;
+            }
+        } else if file_type.is_file() {
+            if rel == MANIFEST || filter.is_excluded(&rel, false) || !filter.is_included(&rel) {
+                debug!("Skipping {}", rel);
+                continue;
+            }
+            entries.push((rel, fs::read(&path)?));
+        } else {
+            debug!("Skipping {} (not a regular file)", rel);
         }
     }
+    Ok(())
+}
+
+/// Describe `(entry name, content)` pairs, parsing each template to record
+/// the name it defines.
+pub fn manifest_for(info: PackageInfo, entries: &[(String, Vec<u8>)]) -> Result<PackageManifest> {
+    let contents = entries
+        .iter()
+        .map(|(name, content)| {
+            if let Some(theme) = theme_name(name) {
+                parse_theme(name, theme, content)?;
+            }
+            let template = if name.ends_with(".tpl") {
+                let text = std::str::from_utf8(content)
+                    .with_context(|| format!("Template is not UTF-8: {}", name))?;
+                let tpl: Template = toml::from_str(text)
+                    .with_context(|| format!("Failed to parse template: {}", name))?;
+                Some(tpl.manifest.name)
+            } else {
+                None
+            };
+            Ok(PackageEntry {
+                path: name.clone(),
+                template,
+                sha256: digest::sha256_hex(content),
+            })
+        })
+        .collect::<Result<_>>()?;
+    Ok(PackageManifest {
+        package: info,
+        contents,
+    })
+}
+
+/// Write `package.toml` and `(entry name, content)` pairs into a new .pkg
+/// archive with the default compression.
+pub fn write_package(
+    output_file: &Path,
+    manifest: &PackageManifest,
+    entries: &[(String, Vec<u8>)],
+) -> Result<()> {
+    write_package_with(output_file, manifest, entries, Compression::default())
+}
+
+/// Write a .pkg archive with fixed timestamps and permissions, so identical
+/// input gives identical bytes. Entries are written in the order given.
+pub fn write_package_with(
+    output_file: &Path,
+    manifest: &PackageManifest,
+    entries: &[(String, Vec<u8>)],
+    compression: Compression,
+) -> Result<()> {
+    let file = File::create(output_file).context("Failed to create output file")?;
+    let mut zip = ZipWriter::new(file);
+    let options = SimpleFileOptions::default()
+        .compression_method(compression.into())
+        .last_modified_time(DateTime::default())
+        .unix_permissions(0o644);
+
+    zip.start_file(MANIFEST, options)?;
+    zip.write_all(toml::to_string_pretty(manifest)?.as_bytes())?;
+
+    for (name, content) in entries {
+        debug!("Adding file: {}", name);
+        zip.start_file(name.as_str(), options)?;
+        zip.write_all(content)?;
+    }
 
     zip.finish()?;
     Ok(())
 }
 
-/// Unpack a .pkg archive into the target directory.
+/// `.hyprinkignore` rules plus `--include` globs.
+///
+/// Ignore lines follow gitignore: a pattern without `/` matches a name at any
+/// depth, a leading `/` anchors it to the source directory, a trailing `/`
+/// only matches directories, and `!` re-includes. The last matching line wins.
+#[derive(Default)]
+struct Filter {
+    rules: Vec<Rule>,
+    include: Vec<GlobMatcher>,
+}
+
+struct Rule {
+    matcher: GlobMatcher,
+    negate: bool,
+    dir_only: bool,
+}
+
+impl Filter {
+    fn exclude(&mut self, line: &str) -> Result<()> {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            return Ok(());
+        }
+        let (negate, pattern) = match line.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, line),
+        };
+        let (dir_only, pattern) = match pattern.strip_suffix('/') {
+            Some(rest) => (true, rest),
+            None => (false, pattern),
+        };
+        let pattern = match pattern.strip_prefix('/') {
+            Some(anchored) => anchored.to_string(),
+            None if pattern.contains('/') => pattern.to_string(),
+            None => format!("**/{}", pattern),
+        };
+        self.rules.push(Rule {
+            matcher: glob(&pattern)?,
+            negate,
+            dir_only,
+        });
+        Ok(())
+    }
+
+    fn include(&mut self, pattern: &str) -> Result<()> {
+        self.include.push(glob(pattern)?);
+        Ok(())
+    }
+
+    fn is_excluded(&self, rel: &str, is_dir: bool) -> bool {
+        self.rules
+            .iter()
+            .rev()
+            .find(|r| (is_dir || !r.dir_only) && r.matcher.is_match(rel))
+            .is_some_and(|r| !r.negate)
+    }
+
+    fn is_included(&self, rel: &str) -> bool {
+        self.include.is_empty() || self.include.iter().any(|g| g.is_match(rel))
+    }
+}
+
+/// `*` and `?` stop at `/`, as in gitignore; only `**` crosses directories.
+fn glob(pattern: &str) -> Result<GlobMatcher> {
+    Ok(GlobBuilder::new(pattern)
+        .literal_separator(true)
+        .build()
+        .with_context(|| format!("Invalid pattern '{}'", pattern))?
+        .compile_matcher())
+}
+
+/// Raw text of every .tpl inside a .pkg archive, as `(entry name, content)` pairs.
+pub fn read_entries(package_file: &Path) -> Result<Vec<(String, String)>> {
+    read_files(package_file)?
+        .into_iter()
+        .filter(|(name, _)| name.ends_with(".tpl"))
+        .map(|(name, content)| {
+            let text = String::from_utf8(content)
+                .with_context(|| format!("Template is not UTF-8: {}", name))?;
+            Ok((name, text))
+        })
+        .collect()
+}
+
+/// Every file inside a .pkg archive except `package.toml`.
+pub fn read_files(package_file: &Path) -> Result<Vec<(String, Vec<u8>)>> {
+    debug!("Reading files from {:?}", package_file);
+    let file = File::open(package_file).context("Failed to open package file")?;
+    let mut archive = ZipArchive::new(file)?;
+    check_archive(&mut archive)?;
+    let mut files = Vec::new();
+
+    let mut remaining = MAX_UNPACKED_SIZE;
+    for i in 0..archive.len() {
+        let file = archive.by_index(i)?;
+        if file.is_dir() || file.name() == MANIFEST {
+            continue;
+        }
+        let name = file.name().to_string();
+        let content = read_capped(file, &mut remaining)?;
+        files.push((name, content));
+    }
+
+    Ok(files)
+}
+
+/// The `package.toml` of a .pkg archive, if it has one.
+pub fn read_manifest(package_file: &Path) -> Result<Option<PackageManifest>> {
+    let file = File::open(package_file).context("Failed to open package file")?;
+    let mut archive = ZipArchive::new(file)?;
+    check_archive(&mut archive)?;
+    let Ok(entry) = archive.by_name(MANIFEST) else {
+        return Ok(None);
+    };
+    let mut remaining = MAX_UNPACKED_SIZE;
+    let content = read_capped(entry, &mut remaining)?;
+    let text = String::from_utf8(content)
+        .with_context(|| format!("{} in {:?} is not UTF-8", MANIFEST, package_file))?;
+    let manifest = toml::from_str(&text)
+        .with_context(|| format!("Failed to parse {} in {:?}", MANIFEST, package_file))?;
+    Ok(Some(manifest))
+}
+
+/// Read a .pkg archive, checking its templates against `package.toml`: every
+/// listed file must be present with the recorded checksum and define the
+/// recorded template, no unlisted templates may be present, and this hyprink
+/// must be at least `min_hyprink`.
+pub fn read_package(package_file: &Path) -> Result<Package> {
+    let manifest = read_manifest(package_file)?;
+    let files = read_files(package_file)?;
+
+    if let Some(manifest) = &manifest {
+        check_compatible(&manifest.package)?;
+
+        let mut found: HashMap<&str, &[u8]> = files
+            .iter()
+            .map(|(name, content)| (name.as_str(), content.as_slice()))
+            .collect();
+        for listed in &manifest.contents {
+            let content = found.remove(listed.path.as_str()).ok_or_else(|| {
+                anyhow!(
+                    "{} lists {} but the package does not contain it",
+                    MANIFEST,
+                    listed.path
+                )
+            })?;
+            if digest::sha256_hex(content) != listed.sha256 {
+                bail!(
+                    "{} does not match its checksum in {}",
+                    listed.path,
+                    MANIFEST
+                );
+            }
+        }
+        if let Some(extra) = found.keys().min() {
+            bail!("{} is not listed in {}", extra, MANIFEST);
+        }
+    }
+
+    let themes = files
+        .iter()
+        .filter_map(|(name, content)| {
+            let theme = theme_name(name)?;
+            Some(parse_theme(name, theme, content).map(|text| (theme.to_string(), text)))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let templates = files
+        .into_iter()
+        .filter(|(name, _)| name.ends_with(".tpl"))
+        .map(|(name, content)| {
+            let content = String::from_utf8(content)
+                .with_context(|| format!("Template is not UTF-8: {}", name))?;
+            let tpl: Template = toml::from_str(&content)
+                .with_context(|| format!("Failed to parse template inside package: {}", name))?;
+            Ok((name, tpl))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    if let Some(manifest) = &manifest {
+        for (name, tpl) in &templates {
+            let listed = manifest
+                .contents
+                .iter()
+                .find(|e| e.path == *name)
+                .and_then(|e| e.template.as_deref());
+            if listed != Some(tpl.manifest.name.as_str()) {
+                bail!(
+                    "{} defines {} but {} says it is {}",
+                    name,
+                    tpl.manifest.name,
+                    MANIFEST,
+                    listed.unwrap_or("not a template")
+                );
+            }
+        }
+    }
+
+    Ok(Package {
+        manifest,
+        templates,
+        themes,
+    })
+}
+
+/// The theme an entry defines: `themes/<name>.toml` gives `<name>`.
+pub fn theme_name(entry: &str) -> Option<&str> {
+    let name = entry
+        .strip_prefix(THEMES_DIR)?
+        .strip_prefix('/')?
+        .strip_suffix(".toml")?;
+    (!name.is_empty() && !name.contains('/')).then_some(name)
+}
+
+fn parse_theme(name: &str, theme: &str, content: &[u8]) -> Result<String> {
+    themes::check_name(theme)?;
+    let text = String::from_utf8(content.to_vec())
+        .with_context(|| format!("Theme is not UTF-8: {}", name))?;
+    ThemeFile::parse(&text).with_context(|| format!("Failed to parse theme: {}", name))?;
+    Ok(text)
+}
+
+/// Parse every .tpl inside a .pkg archive, returning `(entry name, template)` pairs.
+pub fn read_templates(package_file: &Path) -> Result<Vec<(String, Template)>> {
+    Ok(read_package(package_file)?.templates)
+}
+
+fn check_compatible(info: &PackageInfo) -> Result<()> {
+    let Some(required) = &info.min_hyprink else {
+        return Ok(());
+    };
+    let required = template::parse_version(required)
+        .ok_or_else(|| anyhow!("{} has an invalid min_hyprink '{}'", info.name, required))?;
+    let current = template::parse_version(env!("CARGO_PKG_VERSION")).expect("crate version");
+    if current < required {
+        bail!(
+            "{} v{} requires hyprink {} or newer (this is {})",
+            info.name,
+            info.version,
+            required,
+            current
+        );
+    }
+    Ok(())
+}
+
+/// Unpack a .pkg archive into the target directory. The whole archive is
+/// checked first, so a package with any unsafe entry extracts nothing.
 pub fn unpack(package_file: &Path, target_dir: &Path) -> Result<()> {
     debug!("Unpacking {:?} to {:?}", package_file, target_dir);
     let file = File::open(package_file).context("Failed to open package file")?;
     let mut archive = ZipArchive::new(file)?;
+    let names = check_archive(&mut archive)?;
 
     fs::create_dir_all(target_dir)?;
 
-    for i in 0..archive.len() {
-        let mut file = archive.by_index(i)?;
-        let outpath = target_dir.join(file.name());
+    let mut remaining = MAX_UNPACKED_SIZE;
+    for (i, name) in names.into_iter().enumerate() {
+        let file = archive.by_index(i)?;
+        let outpath = target_dir.join(name);
         debug!("Extracting file: {}", file.name());
 
-        let mut content = Vec::new();
-        file.read_to_end(&mut content)?;
-        fs::write(outpath, content)?;
+        if file.is_dir() {
+            fs::create_dir_all(&outpath)?;
+            continue;
+        }
+        if let Some(parent) = outpath.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        fs::write(outpath, read_capped(file, &mut remaining)?)?;
     }
 
     Ok(())
 }
 
+/// Read one entry, taking its size from the `remaining` budget. Sizes in the
+/// headers are only claims, so reading stops at the limit regardless.
+fn read_capped(entry: impl Read, remaining: &mut u64) -> Result<Vec<u8>> {
+    let mut content = Vec::new();
+    entry.take(*remaining + 1).read_to_end(&mut content)?;
+    *remaining = remaining
+        .checked_sub(content.len() as u64)
+        .ok_or(UnpackError::TooLarge)?;
+    Ok(content)
+}
+
+/// Check entry count, declared size and every entry name, returning the
+/// sanitized relative path of each entry in archive order.
+fn check_archive<R: Read + Seek>(archive: &mut ZipArchive<R>) -> Result<Vec<PathBuf>, UnpackError> {
+    if archive.len() > MAX_ENTRIES {
+        return Err(UnpackError::TooManyEntries {
+            count: archive.len(),
+        });
+    }
+
+    let mut names = Vec::with_capacity(archive.len());
+    let mut unsafe_entries = Vec::new();
+    let mut total: u64 = 0;
+    for i in 0..archive.len() {
+        let file = match archive.by_index_raw(i) {
+            Ok(file) => file,
+            Err(e) => {
+                unsafe_entries.push(format!("#{} ({})", i, e));
+                continue;
+            }
+        };
+        total = total.saturating_add(file.size());
+        match safe_name(file.name()) {
+            Some(name) if !file.is_symlink() => names.push(name),
+            Some(_) => unsafe_entries.push(format!("{} (symlink)", file.name())),
+            None => unsafe_entries.push(file.name().to_string()),
+        }
+    }
+
+    if !unsafe_entries.is_empty() {
+        return Err(UnpackError::UnsafeEntries(unsafe_entries));
+    }
+    if total > MAX_UNPACKED_SIZE {
+        return Err(UnpackError::TooLarge);
+    }
+    Ok(names)
+}
+
+/// `name` as a relative path that stays inside the directory it is joined
+/// onto, or `None` if it is absolute, climbs with `..`, or is empty.
+fn safe_name(name: &str) -> Option<PathBuf> {
+    if name.contains('\\') || name.contains('\0') {
+        return None;
+    }
+    let mut path = PathBuf::new();
+    for component in Path::new(name).components() {
+        match component {
+            Component::Normal(part) => path.push(part),
+            Component::CurDir => {}
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+    (!path.as_os_str().is_empty()).then_some(path)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -73,13 +660,153 @@ description = "Test template"
 "#;
         fs::write(source_dir.join("test.tpl"), tpl_content).unwrap();
 
-        pack(&source_dir, &output_file).unwrap();
+        let manifest = pack(&source_dir, &output_file).unwrap();
         assert!(output_file.exists());
+        assert_eq!(manifest.package.name, "source");
+        assert_eq!(manifest.contents[0].template.as_deref(), Some("test"));
 
         unpack(&output_file, &unpack_dir).unwrap();
         assert!(unpack_dir.join("test.tpl").exists());
+        assert!(unpack_dir.join(MANIFEST).exists());
 
         let content = fs::read_to_string(unpack_dir.join("test.tpl")).unwrap();
         assert!(content.contains("name = \"test\""));
     }
+
+    #[test]
+    fn test_pack_is_recursive_filtered_and_reproducible() {
+        let dir = tempdir().unwrap();
+        let src = dir.path().join("theme");
+        let tpl =
+            "[manifest]\nname = \"bar\"\nversion = \"1.0.0\"\nauthors = []\ndescription = \"\"\n";
+        fs::create_dir_all(src.join("waybar/icons")).unwrap();
+        fs::create_dir_all(src.join("build")).unwrap();
+        fs::create_dir_all(src.join(THEMES_DIR)).unwrap();
+        fs::write(
+            src.join("themes/dracula.toml"),
+            "[theme]\ncolors = { bg = \"#282a36\" }\n",
+        )
+        .unwrap();
+        fs::write(src.join("waybar/bar.tpl"), tpl).unwrap();
+        fs::write(src.join("waybar/icons/logo.png"), [0u8, 159, 146, 150]).unwrap();
+        fs::write(src.join("waybar/notes.bak"), "x").unwrap();
+        fs::write(src.join("waybar/keep.bak"), "x").unwrap();
+        fs::write(src.join("build/out.txt"), "x").unwrap();
+        fs::write(src.join(IGNORE_FILE), "*.bak\n!keep.bak\nbuild/\n").unwrap();
+
+        let first = dir.path().join("a.pkg");
+        let second = dir.path().join("b.pkg");
+        let manifest = pack(&src, &first).unwrap();
+        let paths: Vec<_> = manifest.contents.iter().map(|e| e.path.as_str()).collect();
+        assert_eq!(
+            paths,
+            vec![
+                "themes/dracula.toml",
+                "waybar/bar.tpl",
+                "waybar/icons/logo.png",
+                "waybar/keep.bak"
+            ]
+        );
+
+        std::thread::sleep(std::time::Duration::from_millis(1100));
+        pack(&src, &second).unwrap();
+        assert_eq!(fs::read(&first).unwrap(), fs::read(&second).unwrap());
+
+        let package = read_package(&first).unwrap();
+        assert_eq!(package.templates[0].0, "waybar/bar.tpl");
+        assert_eq!(package.themes[0].0, "dracula");
+
+        let opts = PackOptions {
+            include: vec!["**/*.tpl".to_string()],
+            compression: Compression::Zstd,
+            ..Default::default()
+        };
+        let manifest = pack_with(&src, &second, &opts).unwrap();
+        assert_eq!(manifest.contents.len(), 1);
+        read_package(&second).unwrap();
+
+        let mut filter = Filter::default();
+        filter.exclude("/waybar/*.png").unwrap();
+        filter.include("waybar/*").unwrap();
+        assert!(filter.is_excluded("waybar/logo.png", false));
+        assert!(!filter.is_excluded("waybar/icons/logo.png", false));
+        assert!(filter.is_included("waybar/bar.tpl"));
+        assert!(!filter.is_included("waybar/icons/logo.png"));
+    }
+
+    #[test]
+    fn test_unpack_rejects_unsafe_entries() {
+        let dir = tempdir().unwrap();
+        let pkg = dir.path().join("evil.pkg");
+        let target = dir.path().join("out");
+
+        let mut zip = ZipWriter::new(File::create(&pkg).unwrap());
+        let options = SimpleFileOptions::default();
+        for name in ["themes/ok.toml", "../../.bashrc", "/etc/passwd"] {
+            zip.start_file(name, options).unwrap();
+            zip.write_all(b"x").unwrap();
+        }
+        zip.add_symlink("link", "/etc", options).unwrap();
+        zip.finish().unwrap();
+
+        let err = unpack(&pkg, &target).unwrap_err();
+        match err.downcast_ref::<UnpackError>() {
+            Some(UnpackError::UnsafeEntries(entries)) => {
+                assert_eq!(entries, &["../../.bashrc", "/etc/passwd", "link (symlink)"])
+            }
+            other => panic!("unexpected error: {:?}", other),
+        }
+        assert!(!target.exists());
+        assert!(read_manifest(&pkg).is_err());
+
+        // Whatever the headers claim, reading stops once the budget is spent
+        let mut remaining = 4;
+        assert_eq!(read_capped(&b"abcd"[..], &mut remaining).unwrap(), b"abcd");
+        let err = read_capped(&b"e"[..], &mut remaining).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<UnpackError>(),
+            Some(UnpackError::TooLarge)
+        ));
+
+        assert_eq!(safe_name("./a/b.tpl"), Some(PathBuf::from("a/b.tpl")));
+        assert_eq!(safe_name("a/../b"), None);
+    }
+
+    #[test]
+    fn test_manifest_is_enforced() {
+        let dir = tempdir().unwrap();
+        let pkg = dir.path().join("demo.pkg");
+        let tpl =
+            "[manifest]\nname = \"demo\"\nversion = \"1.0.0\"\nauthors = []\ndescription = \"\"\n";
+        let entries = vec![("demo.tpl".to_string(), tpl.as_bytes().to_vec())];
+        let mut info = PackageInfo::new("demo-pkg", "2.0.0");
+        let manifest = manifest_for(info.clone(), &entries).unwrap();
+
+        write_package(&pkg, &manifest, &entries).unwrap();
+        let package = read_package(&pkg).unwrap();
+        assert_eq!(package.manifest.unwrap().package.name, "demo-pkg");
+        assert_eq!(package.templates[0].1.manifest.name, "demo");
+
+        let tampered = vec![(
+            "demo.tpl".to_string(),
+            tpl.replace("1.0.0", "1.0.1").into_bytes(),
+        )];
+        write_package(&pkg, &manifest, &tampered).unwrap();
+        let err = read_package(&pkg).unwrap_err().to_string();
+        assert!(err.contains("checksum"), "{}", err);
+
+        info.min_hyprink = Some("999.0".to_string());
+        let manifest = manifest_for(info, &entries).unwrap();
+        write_package(&pkg, &manifest, &entries).unwrap();
+        let err = read_package(&pkg).unwrap_err().to_string();
+        assert!(err.contains("requires hyprink 999.0.0"), "{}", err);
+
+        let bad_theme = vec![(
+            "themes/.hidden.toml".to_string(),
+            b"[theme]\nname = \"x\"\n".to_vec(),
+        )];
+        let info = PackageInfo::new("demo-pkg", "2.0.0");
+        let err = manifest_for(info, &bad_theme).unwrap_err().to_string();
+        assert!(err.contains("invalid theme name"), "{}", err);
+    }
 }
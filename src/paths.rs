@@ -0,0 +1,232 @@
+use crate::config::{self, PathsSection};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum PathPolicyError {
+    #[error("Target {target} resolves to {path:?}, outside the allowed roots")]
+    OutsideRoots { target: String, path: PathBuf },
+}
+
+/// Decides where template targets may be written. Targets are resolved to
+/// canonical paths and must live under one of the allowed roots unless
+/// `allow_outside` is set. With a staging root, every resolved path is
+/// redirected beneath it instead of touching the real filesystem.
+#[derive(Debug, Clone)]
+pub struct PathPolicy {
+    roots: Vec<PathBuf>,
+    allow_outside: bool,
+    staging: Option<PathBuf>,
+}
+
+impl Default for PathPolicy {
+    fn default() -> Self {
+        Self {
+            roots: default_roots(),
+            allow_outside: false,
+            staging: None,
+        }
+    }
+}
+
+impl PathPolicy {
+    /// Build the policy from `[paths]` in hyprink.conf, honouring `HYPRINK_ROOT`.
+    pub fn from_config(section: &PathsSection) -> Self {
+        let mut policy = Self::default();
+        policy.roots.extend(
+            section
+                .allowed_roots
+                .iter()
+                .map(|r| canonical_path(&expand_target(r))),
+        );
+        policy.allow_outside = section.allow_outside;
+        policy.staging = std::env::var_os("HYPRINK_ROOT")
+            .filter(|v| !v.is_empty())
+            .map(PathBuf::from);
+        policy.with_staging(None)
+    }
+
+    /// Redirect all output beneath `root`; `None` keeps the current setting.
+    pub fn with_staging(mut self, root: Option<PathBuf>) -> Self {
+        if let Some(root) = root {
+            self.staging = Some(root);
+        }
+        self.staging = self.staging.map(|r| canonical_path(&absolute(r)));
+        self
+    }
+
+    /// Resolve to the real paths even if a staging root is configured.
+    pub fn without_staging(mut self) -> Self {
+        self.staging = None;
+        self
+    }
+
+    pub fn staging(&self) -> Option<&Path> {
+        self.staging.as_deref()
+    }
+
+    /// Resolve `target` to the path that should actually be written.
+    pub fn resolve(&self, target: &str) -> Result<PathBuf, PathPolicyError> {
+        let path = canonical_target(target);
+
+        if !self.allow_outside && !self.roots.iter().any(|root| path.starts_with(root)) {
+            return Err(PathPolicyError::OutsideRoots {
+                target: target.to_string(),
+                path,
+            });
+        }
+
+        Ok(match &self.staging {
+            Some(staging) => staging.join(path.strip_prefix("/").unwrap_or(&path)),
+            None => path,
+        })
+    }
+}
+
+/// `$HOME` plus the XDG config, data, cache and state directories.
+pub fn default_roots() -> Vec<PathBuf> {
+    let home = dirs_next::home_dir();
+    let xdg = |var: &str, fallback: Option<PathBuf>| {
+        std::env::var_os(var)
+            .filter(|v| !v.is_empty())
+            .map(PathBuf::from)
+            .or(fallback)
+    };
+
+    let mut roots: Vec<PathBuf> = [
+        home.clone(),
+        xdg("XDG_CONFIG_HOME", dirs_next::config_dir()),
+        xdg("XDG_DATA_HOME", dirs_next::data_dir()),
+        xdg("XDG_CACHE_HOME", dirs_next::cache_dir()),
+        xdg("XDG_STATE_HOME", home.map(|h| h.join(".local/state"))),
+    ]
+    .into_iter()
+    .flatten()
+    .map(|p| canonical_path(&p))
+    .collect();
+    roots.dedup();
+    roots
+}
+
+/// Expand `~` in a template target; relative targets are taken from `$HOME`.
+pub fn expand_target(target: &str) -> PathBuf {
+    let path = match target.strip_prefix('~') {
+        Some("") => dirs_next::home_dir().unwrap_or_else(|| PathBuf::from(target)),
+        Some(rest) if rest.starts_with('/') => config::expand_path(target),
+        _ => PathBuf::from(target),
+    };
+
+    if path.is_absolute() {
+        path
+    } else {
+        dirs_next::home_dir()
+            .map(|home| home.join(&path))
+            .unwrap_or_else(|| absolute(path))
+    }
+}
+
+/// Resolve a target to the path it identifies on disk. `.` and `..` are folded
+/// lexically and the deepest existing ancestor is canonicalized, so symlinked
+/// directories and not-yet-created files compare equal.
+pub fn canonical_target(target: &str) -> PathBuf {
+    canonical_path(&expand_target(target))
+}
+
+/// [`canonical_target`] for an already absolute path.
+pub fn canonical_path(path: &Path) -> PathBuf {
+    canonicalize_lossy(&normalize(path))
+}
+
+/// Fold `.` and `..` components without touching the filesystem.
+pub fn normalize(path: &Path) -> PathBuf {
+    let mut out = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !out.pop() {
+                    out.push(component);
+                }
+            }
+            other => out.push(other),
+        }
+    }
+    out
+}
+
+fn absolute(path: PathBuf) -> PathBuf {
+    if path.is_absolute() {
+        return path;
+    }
+    std::env::current_dir()
+        .map(|cwd| cwd.join(&path))
+        .unwrap_or(path)
+}
+
+fn canonicalize_lossy(path: &Path) -> PathBuf {
+    let mut existing = path;
+    let mut rest = Vec::new();
+    loop {
+        if let Ok(canonical) = fs::canonicalize(existing) {
+            return rest.iter().rev().fold(canonical, |acc, c| acc.join(c));
+        }
+        match (existing.parent(), existing.file_name()) {
+            (Some(parent), Some(name)) => {
+                rest.push(name.to_os_string());
+                existing = parent;
+            }
+            _ => return path.to_path_buf(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn policy(roots: Vec<PathBuf>) -> PathPolicy {
+        PathPolicy {
+            roots: roots.iter().map(|r| canonical_path(r)).collect(),
+            allow_outside: false,
+            staging: None,
+        }
+    }
+
+    #[test]
+    fn test_resolve_rejects_traversal_out_of_root() {
+        let dir = tempdir().unwrap();
+        let root = dir.path().join("home");
+        fs::create_dir_all(&root).unwrap();
+        let policy = policy(vec![root.clone()]);
+
+        let inside = format!("{}/.config/app/../app/theme.conf", root.display());
+        assert_eq!(
+            policy.resolve(&inside).unwrap(),
+            canonical_path(&root).join(".config/app/theme.conf")
+        );
+
+        let escape = format!("{}/../../etc/passwd", root.display());
+        assert!(matches!(
+            policy.resolve(&escape),
+            Err(PathPolicyError::OutsideRoots { .. })
+        ));
+        assert!(policy.resolve("/etc/passwd").is_err());
+    }
+
+    #[test]
+    fn test_resolve_redirects_into_staging_root() {
+        let dir = tempdir().unwrap();
+        let root = dir.path().join("home");
+        let staging = dir.path().join("stage");
+        fs::create_dir_all(&root).unwrap();
+        let policy = policy(vec![root.clone()]).with_staging(Some(staging.clone()));
+
+        let resolved = policy
+            .resolve(&format!("{}/.config/waybar/style.css", root.display()))
+            .unwrap();
+        assert!(resolved.starts_with(canonical_path(&staging)));
+        assert!(resolved.ends_with(".config/waybar/style.css"));
+    }
+}
@@ -1,35 +1,107 @@
 use anyhow::{Context, Result};
 use log::debug;
+use serde::{Deserialize, Serialize};
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::Command;
 use tera::{Context as TeraContext, Tera};
 
 use crate::config::Config;
+use crate::digest;
+use crate::fonts;
 use crate::logger;
+use crate::paths::PathPolicy;
 use crate::template::Template;
 
 use std::collections::HashMap;
 use tera::{Value, to_value, try_get_value};
 
-pub fn apply(template: &Template, config: &Config, _force: bool) -> Result<bool> {
-    debug!("Applying template: {}", template.manifest.name);
+/// Per-run settings for [`apply`].
+#[derive(Debug, Clone, Default)]
+pub struct ApplyOptions {
+    /// Overwrite targets even if they were edited since the last apply.
+    pub force: bool,
+    pub policy: PathPolicy,
+    /// Checksum of what was last written to each path (see `Store::written_checksums`).
+    pub last_written: HashMap<PathBuf, String>,
+    /// [`hook_digest`] of the reload hook approved for each template. Hooks
+    /// without a matching entry are not run.
+    pub approved_hooks: HashMap<String, String>,
+}
+
+/// What [`apply`] did for one template.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct TemplateOutcome {
+    /// Files written, in the order they were rendered.
+    pub written: Vec<WrittenFile>,
+    /// Targets edited on disk since they were last written. When not empty,
+    /// nothing was written and the hook did not run.
+    pub modified: Vec<PathBuf>,
+    /// The reload hook, if one ran.
+    pub hook: Option<HookOutcome>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct WrittenFile {
+    pub target: String,
+    pub path: PathBuf,
+    /// SHA-256 of the rendered content.
+    pub checksum: String,
+}
+
+impl TemplateOutcome {
+    /// Whether the reload hook ran and failed.
+    pub fn hook_failed(&self) -> bool {
+        self.hook
+            .as_ref()
+            .is_some_and(|hook| hook.approved && !hook.success)
+    }
+
+    /// Whether the reload hook was not run because it is not approved.
+    pub fn hook_unapproved(&self) -> bool {
+        self.hook.as_ref().is_some_and(|hook| !hook.approved)
+    }
+}
+
+/// Result of running a template's reload hook.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HookOutcome {
+    pub command: String,
+    pub success: bool,
+    /// `None` when the hook was killed by a signal or did not run.
+    pub exit_code: Option<i32>,
+    pub duration_ms: u64,
+    /// `false` when the hook was not run because it differs from the approved one.
+    pub approved: bool,
+}
+
+/// Identifies a hook command for approval: its SHA-256 in hex.
+pub fn hook_digest(command: &str) -> String {
+    digest::sha256_hex(command.as_bytes())
+}
+
+/// Tera instance and context that templates are rendered with for `config`.
+pub fn render_context(config: &Config) -> (Tera, TeraContext) {
     let mut tera = Tera::default();
     tera.register_filter("hex_to_rgb", hex_to_rgb);
     tera.register_filter("hex_to_godot_color", hex_to_godot_color);
+    fonts::register_filters(&mut tera);
 
     let mut ctx = TeraContext::new();
 
     // Context Setup
     ctx.insert("colors", &config.theme.colors);
-    ctx.insert("fonts", &config.theme.fonts);
+    ctx.insert("fonts", &config.theme.font_strings());
+    ctx.insert("font", &config.theme.font_roles());
 
-    let active_icons = if config.theme.active_icons == "nerdfont" {
-        &config.icons.nerdfont
-    } else {
-        &config.icons.ascii
-    };
-    ctx.insert("icons", active_icons);
+    ctx.insert("icons", &config.icons.resolve(&config.theme.active_icons));
+
+    (tera, ctx)
+}
+
+pub fn apply(template: &Template, config: &Config, opts: &ApplyOptions) -> Result<TemplateOutcome> {
+    debug!("Applying template: {}", template.manifest.name);
+    let (mut tera, mut ctx) = render_context(config);
 
     if log::log_enabled!(log::Level::Debug) {
         debug!(
@@ -38,7 +110,7 @@ pub fn apply(template: &Template, config: &Config, _force: bool) -> Result<bool>
         );
     }
 
-    process_template(template, &mut tera, &mut ctx, config)
+    process_template(template, &mut tera, &mut ctx, opts)
 }
 
 /// Tera filter: hex_to_rgb
@@ -82,24 +154,51 @@ fn process_template(
     tpl: &Template,
     tera: &mut Tera,
     ctx: &mut TeraContext,
-    _config: &Config,
-) -> Result<bool> {
+    opts: &ApplyOptions,
+) -> Result<TemplateOutcome> {
     debug!(
         "Processing template targets and hooks for: {}",
         tpl.manifest.name
     );
 
-    // Render Targets
-    for target in &tpl.targets {
-        render_and_write(&target.target, &target.content, tera, ctx)?;
+    let mut outcome = TemplateOutcome::default();
+
+    // Render everything first so a broken target leaves no file half-updated
+    let mut rendered = Vec::new();
+    for target in tpl.outputs() {
+        let (path, content) =
+            render_target(&target.target, &target.content, tera, ctx, &opts.policy)?;
+        rendered.push((target.target.as_str(), path, content));
+    }
+
+    // Refuse to clobber files edited by hand since hyprink last wrote them
+    if !opts.force && opts.policy.staging().is_none() {
+        outcome.modified = rendered
+            .iter()
+            .filter(|(_, path, content)| is_modified(path, content, opts.last_written.get(path)))
+            .map(|(_, path, _)| path.clone())
+            .collect();
+        if !outcome.modified.is_empty() {
+            return Ok(outcome);
+        }
     }
 
-    // Render Files
-    for file in &tpl.files {
-        render_and_write(&file.target, &file.content, tera, ctx)?;
+    for (target, path, content) in rendered {
+        write_target(&path, &content)?;
+        outcome.written.push(WrittenFile {
+            target: target.to_string(),
+            path,
+            checksum: digest::sha256_hex(content.as_bytes()),
+        });
     }
 
-    let mut hooks_success = true;
+    // Hooks act on the live system, so staged runs leave them out
+    if let Some(staging) = opts.policy.staging() {
+        if tpl.hooks.reload.is_some() {
+            debug!("Skipping reload hook (staging into {:?})", staging);
+        }
+        return Ok(outcome);
+    }
 
     // Run Hooks
     if let Some(cmd) = &tpl.hooks.reload {
@@ -107,6 +206,20 @@ fn process_template(
 
         let name = &tpl.manifest.name;
 
+        // Only the exact command the user approved may run; the caller
+        // reports the refusal from the outcome
+        if opts.approved_hooks.get(name) != Some(&hook_digest(cmd)) {
+            debug!("Not running unapproved hook of {}: {}", name, cmd);
+            outcome.hook = Some(HookOutcome {
+                command: cmd.clone(),
+                success: false,
+                exit_code: None,
+                duration_ms: 0,
+                approved: false,
+            });
+            return Ok(outcome);
+        }
+
         // Log hook execution
         let run_msg = format!("[{}] running: {}", name, cmd);
         logger::info("HOOK", &run_msg);
@@ -151,38 +264,55 @@ fn process_template(
             let exit_code = output.status.code().unwrap_or(-1);
             let fail_msg = format!("[{}] failed (exit {}): {}", name, exit_code, cmd);
             logger::error("HOOK", &fail_msg);
-            hooks_success = false;
         }
+
+        outcome.hook = Some(HookOutcome {
+            command: cmd.clone(),
+            success: output.status.success(),
+            exit_code: output.status.code(),
+            duration_ms: duration.as_millis() as u64,
+            approved: true,
+        });
     }
 
-    Ok(hooks_success)
+    Ok(outcome)
 }
 
-fn render_and_write(target: &str, content: &str, tera: &mut Tera, ctx: &TeraContext) -> Result<()> {
+fn render_target(
+    target: &str,
+    content: &str,
+    tera: &mut Tera,
+    ctx: &TeraContext,
+    policy: &PathPolicy,
+) -> Result<(PathBuf, String)> {
     debug!("Rendering target: {}", target);
 
-    let target_expanded = if target.starts_with("~") {
-        let home = directories::UserDirs::new()
-            .context("Could not determine home directory")?
-            .home_dir()
-            .to_string_lossy()
-            .to_string();
-        target.replace("~", &home)
-    } else {
-        target.to_string()
-    };
-
-    debug!("Expanded target path: {}", target_expanded);
-
-    let path = PathBuf::from(&target_expanded);
-    if let Some(parent) = path.parent() {
-        fs::create_dir_all(parent)?;
-    }
+    let path = policy.resolve(target)?;
+    debug!("Resolved target path: {:?}", path);
 
     let rendered = tera
         .render_str(content, ctx)
         .context("Failed to render template")?;
 
-    fs::write(path, rendered)?;
+    Ok((path, rendered))
+}
+
+fn write_target(path: &Path, content: &str) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, content)?;
     Ok(())
 }
+
+/// Whether `path` differs from both what was last written there and what is
+/// about to be written. Paths without a record, or that no longer exist, are free.
+fn is_modified(path: &Path, content: &str, last_written: Option<&String>) -> bool {
+    let Some(last) = last_written else {
+        return false;
+    };
+    match digest::sha256_file(path) {
+        Ok(current) => current != *last && current != digest::sha256_hex(content.as_bytes()),
+        Err(_) => false,
+    }
+}
@@ -0,0 +1,433 @@
+//! Package repositories: a directory holding `.pkg` files and an
+//! `index.toml` that lists them.
+//!
+//! A repository is plain files, so a shared directory, an NFS mount or
+//! anything a static file server exports works. Paths in the index are
+//! relative to the directory the index sits in.
+
+use crate::db::Store;
+use crate::digest;
+use crate::packager;
+use crate::template;
+use anyhow::{Context, Result, anyhow, bail};
+use log::debug;
+use semver::{Version, VersionReq};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+/// Name of the index at the root of a repository.
+pub const INDEX: &str = "index.toml";
+
+/// One version of one package in a repository.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexEntry {
+    pub name: String,
+    pub version: String,
+    #[serde(default)]
+    pub description: String,
+    /// Path of the `.pkg`, relative to the index
+    pub file: String,
+    pub sha256: String,
+}
+
+impl IndexEntry {
+    /// Where the package file of this entry lives in `repo`.
+    pub fn path(&self, repo: &Path) -> Result<PathBuf> {
+        let file = Path::new(&self.file);
+        if !file.components().all(|c| matches!(c, Component::Normal(_))) {
+            bail!(
+                "{} lists a file outside the repository: {}",
+                INDEX,
+                self.file
+            );
+        }
+        Ok(repo.join(file))
+    }
+
+    fn semver(&self) -> Option<Version> {
+        template::parse_version(&self.version)
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Index {
+    #[serde(default)]
+    pub packages: Vec<IndexEntry>,
+}
+
+impl Index {
+    pub fn load(repo: &Path) -> Result<Self> {
+        let path = repo.join(INDEX);
+        let text =
+            fs::read_to_string(&path).with_context(|| format!("Failed to read {:?}", path))?;
+        toml::from_str(&text).with_context(|| format!("Failed to parse {:?}", path))
+    }
+
+    pub fn save(&self, repo: &Path) -> Result<()> {
+        let path = repo.join(INDEX);
+        fs::write(&path, toml::to_string_pretty(self)?)
+            .with_context(|| format!("Failed to write {:?}", path))
+    }
+
+    /// Index every `.pkg` below `repo` by the `package.toml` inside it.
+    /// Packages without a manifest cannot be indexed and are skipped.
+    pub fn build(repo: &Path) -> Result<(Self, Vec<PathBuf>)> {
+        let mut index = Index::default();
+        let mut skipped = Vec::new();
+        let mut files = Vec::new();
+        collect_packages(repo, &mut files)?;
+        files.sort();
+
+        for path in files {
+            let Some(manifest) = packager::read_manifest(&path)? else {
+                debug!("Skipping {:?}: no {}", path, packager::MANIFEST);
+                skipped.push(path);
+                continue;
+            };
+            let file = path
+                .strip_prefix(repo)
+                .expect("collected below repo")
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            index.packages.push(IndexEntry {
+                name: manifest.package.name,
+                version: manifest.package.version,
+                description: manifest.package.description,
+                file,
+                sha256: digest::sha256_file(&path)?,
+            });
+        }
+        Ok((index, skipped))
+    }
+
+    /// The newest version of `name` that satisfies `req`.
+    pub fn resolve(&self, name: &str, req: &VersionReq) -> Option<&IndexEntry> {
+        self.packages
+            .iter()
+            .filter(|e| e.name == name)
+            .filter_map(|e| Some((e.semver()?, e)))
+            .filter(|(v, _)| req.matches(v))
+            .max_by(|(a, _), (b, _)| a.cmp(b))
+            .map(|(_, e)| e)
+    }
+
+    /// The newest version of every package whose name or description
+    /// contains `term`, ignoring case.
+    pub fn search(&self, term: &str) -> Vec<&IndexEntry> {
+        let term = term.to_lowercase();
+        let names: BTreeSet<&str> = self
+            .packages
+            .iter()
+            .filter(|e| {
+                e.name.to_lowercase().contains(&term)
+                    || e.description.to_lowercase().contains(&term)
+            })
+            .map(|e| e.name.as_str())
+            .collect();
+        names
+            .into_iter()
+            .filter_map(|name| self.resolve(name, &VersionReq::STAR))
+            .collect()
+    }
+}
+
+/// The newest match across all repositories; on a tie the repository listed
+/// first wins.
+pub fn resolve<'a>(
+    indexes: &'a [(PathBuf, Index)],
+    name: &str,
+    req: &VersionReq,
+) -> Option<(&'a Path, &'a IndexEntry)> {
+    let mut best: Option<(&Path, &IndexEntry)> = None;
+    for (repo, index) in indexes {
+        if let Some(entry) = index.resolve(name, req) {
+            let newer = best.is_none_or(|(_, b)| entry.semver() > b.semver());
+            if newer {
+                best = Some((repo, entry));
+            }
+        }
+    }
+    best
+}
+
+/// The package file of `entry`, once it is known to be what the index says:
+/// present, matching its checksum, and carrying the listed name and version.
+pub fn fetch(repo: &Path, entry: &IndexEntry) -> Result<PathBuf> {
+    let path = entry.path(repo)?;
+    if !path.is_file() {
+        bail!("{} lists {}, which does not exist", INDEX, path.display());
+    }
+    if digest::sha256_file(&path)? != entry.sha256 {
+        bail!(
+            "{} does not match the checksum in {} (re-run `hyprink repo index`)",
+            path.display(),
+            repo.join(INDEX).display()
+        );
+    }
+    let manifest = packager::read_manifest(&path)?
+        .ok_or_else(|| anyhow!("{} has no {}", path.display(), packager::MANIFEST))?;
+    if manifest.package.name != entry.name || manifest.package.version != entry.version {
+        bail!(
+            "{} is {} v{}, but {} lists it as {} v{}",
+            path.display(),
+            manifest.package.name,
+            manifest.package.version,
+            INDEX,
+            entry.name,
+            entry.version
+        );
+    }
+    Ok(path)
+}
+
+/// The newest installed version of every package templates came from.
+pub fn installed_packages(db: &Store) -> BTreeMap<String, Version> {
+    let mut installed: BTreeMap<String, Version> = BTreeMap::new();
+    for tpl in db.list() {
+        let Some(pkg) = db
+            .provenance(&tpl.manifest.name)
+            .and_then(|p| p.package.as_ref())
+        else {
+            continue;
+        };
+        let Some(version) = template::parse_version(&pkg.version) else {
+            continue;
+        };
+        let current = installed.entry(pkg.name.clone()).or_insert(version.clone());
+        if version > *current {
+            *current = version;
+        }
+    }
+    installed
+}
+
+/// Templates installed from `package` that `shipped`, the templates of the
+/// version about to be installed, no longer contains: ones it dropped or
+/// renamed. They are removed first so a renamed template can keep its targets.
+pub fn dropped_templates(db: &Store, package: &str, shipped: &BTreeSet<String>) -> Vec<String> {
+    db.list()
+        .into_iter()
+        .map(|tpl| &tpl.manifest.name)
+        .filter(|name| !shipped.contains(*name))
+        .filter(|name| {
+            db.provenance(name)
+                .and_then(|p| p.package.as_ref())
+                .is_some_and(|pkg| pkg.name == package)
+        })
+        .cloned()
+        .collect()
+}
+
+/// A repository location as given on the command line: a directory, or a
+/// `file://` URL pointing at one.
+pub fn location(arg: &str) -> Result<PathBuf> {
+    let path = match arg.strip_prefix("file://") {
+        Some(rest) => Path::new(rest),
+        None if arg.contains("://") => bail!(
+            "Unsupported repository location {}: use a directory or a file:// URL",
+            arg
+        ),
+        None => Path::new(arg),
+    };
+    let dir = fs::canonicalize(path)
+        .with_context(|| format!("Repository not found: {}", path.display()))?;
+    if !dir.join(INDEX).is_file() {
+        bail!(
+            "{} has no {} (create one with `hyprink repo index {}`)",
+            dir.display(),
+            INDEX,
+            dir.display()
+        );
+    }
+    Ok(dir)
+}
+
+/// Split `name[@version]` into the name and a semver requirement. A bare
+/// version like `1.2` means `^1.2`; no version means any.
+pub fn parse_spec(spec: &str) -> Result<(&str, VersionReq)> {
+    match spec.split_once('@') {
+        Some((name, req)) => {
+            let req = VersionReq::parse(req.trim_start_matches('v'))
+                .with_context(|| format!("Invalid version requirement '{}'", req))?;
+            Ok((name, req))
+        }
+        None => Ok((spec, VersionReq::STAR)),
+    }
+}
+
+fn collect_packages(dir: &Path, out: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            collect_packages(&path, out)?;
+        } else if file_type.is_file() && path.extension().is_some_and(|ext| ext == "pkg") {
+            out.push(path);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{PackageRef, Provenance};
+    use crate::packager::{self, PackageInfo};
+    use tempfile::tempdir;
+
+    /// Pack `templates`, each `(name, target)`, as `name` v`version` into `dir/file`.
+    fn write_pkg(dir: &Path, file: &str, name: &str, version: &str, templates: &[(&str, &str)]) {
+        let entries: Vec<(String, Vec<u8>)> = templates
+            .iter()
+            .map(|(tpl, target)| {
+                let content = format!(
+                    "[manifest]\nname = \"{}\"\nversion = \"{}\"\nauthors = []\ndescription = \"\"\n\n[[targets]]\ntarget = \"{}\"\ncontent = \"x\"\n",
+                    tpl, version, target
+                );
+                (format!("{}.tpl", tpl), content.into_bytes())
+            })
+            .collect();
+        let manifest = packager::manifest_for(PackageInfo::new(name, version), &entries).unwrap();
+        packager::write_package(&dir.join(file), &manifest, &entries).unwrap();
+    }
+
+    /// Store the templates of a fetched package the way `repo install` does,
+    /// returning the ones removed because this version no longer has them.
+    fn install(db: &mut Store, path: &Path) -> Vec<String> {
+        let package = packager::read_package(path).unwrap();
+        let info = package.manifest.unwrap().package;
+        let shipped = package
+            .templates
+            .iter()
+            .map(|(_, tpl)| tpl.manifest.name.clone())
+            .collect();
+        let dropped = dropped_templates(db, &info.name, &shipped);
+        for name in &dropped {
+            db.remove(name);
+        }
+        for (entry, tpl) in package.templates {
+            assert!(db.conflicts_with(&tpl).is_empty());
+            let name = tpl.manifest.name.clone();
+            db.add(tpl).unwrap();
+            db.set_provenance(
+                &name,
+                Provenance {
+                    source: path.to_path_buf(),
+                    entry: Some(entry),
+                    checksum: digest::sha256_file(path).unwrap(),
+                    added_at: 0,
+                    package: Some(PackageRef {
+                        name: info.name.clone(),
+                        version: info.version.clone(),
+                    }),
+                },
+            );
+        }
+        dropped
+    }
+
+    #[test]
+    fn test_index_resolves_newest_matching_version() {
+        let dir = tempdir().unwrap();
+        let tpl =
+            "[manifest]\nname = \"bar\"\nversion = \"1.0.0\"\nauthors = []\ndescription = \"\"\n";
+        let entries = vec![("bar.tpl".to_string(), tpl.as_bytes().to_vec())];
+        fs::create_dir_all(dir.path().join("old")).unwrap();
+        for (file, version) in [
+            ("old/waybar-1.0.0.pkg", "1.0.0"),
+            ("waybar-1.4.2.pkg", "1.4.2"),
+            ("waybar-2.0.0.pkg", "2.0.0"),
+        ] {
+            let mut info = PackageInfo::new("waybar-theme", version);
+            info.description = "Waybar in Dracula colors".to_string();
+            let manifest = packager::manifest_for(info, &entries).unwrap();
+            packager::write_package(&dir.path().join(file), &manifest, &entries).unwrap();
+        }
+
+        let (index, skipped) = Index::build(dir.path()).unwrap();
+        assert!(skipped.is_empty());
+        index.save(dir.path()).unwrap();
+        let index =
+            Index::load(&location(&format!("file://{}", dir.path().display())).unwrap()).unwrap();
+        assert_eq!(index.packages.len(), 3);
+
+        let (name, req) = parse_spec("waybar-theme@1").unwrap();
+        let entry = index.resolve(name, &req).unwrap();
+        assert_eq!(entry.version, "1.4.2");
+        assert!(entry.path(dir.path()).unwrap().is_file());
+
+        let (name, req) = parse_spec("waybar-theme").unwrap();
+        assert_eq!(index.resolve(name, &req).unwrap().version, "2.0.0");
+        assert!(
+            index
+                .resolve("waybar-theme", &VersionReq::parse("^3").unwrap())
+                .is_none()
+        );
+
+        let found = index.search("DRACULA");
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].version, "2.0.0");
+    }
+
+    #[test]
+    fn test_install_and_upgrade_across_repositories() {
+        let old = tempdir().unwrap();
+        let new = tempdir().unwrap();
+        write_pkg(
+            old.path(),
+            "bar-1.0.0.pkg",
+            "bar",
+            "1.0.0",
+            &[
+                ("top", "~/.config/bar/top.css"),
+                ("tray", "~/.config/bar/tray.css"),
+            ],
+        );
+        // `top` is renamed to `panel` and keeps its target; `tray` is dropped
+        write_pkg(
+            new.path(),
+            "bar-1.1.0.pkg",
+            "bar",
+            "1.1.0",
+            &[("panel", "~/.config/bar/top.css")],
+        );
+        let indexes: Vec<(PathBuf, Index)> = [old.path(), new.path()]
+            .into_iter()
+            .map(|repo| (repo.to_path_buf(), Index::build(repo).unwrap().0))
+            .collect();
+
+        let mut db = Store::default();
+        let (repo, entry) =
+            resolve(&indexes, "bar", &VersionReq::parse("=1.0.0").unwrap()).unwrap();
+        assert_eq!(repo, old.path());
+        assert!(install(&mut db, &fetch(repo, entry).unwrap()).is_empty());
+        assert_eq!(installed_packages(&db)["bar"], Version::new(1, 0, 0));
+
+        let (repo, entry) = resolve(&indexes, "bar", &VersionReq::STAR).unwrap();
+        assert_eq!((repo, entry.version.as_str()), (new.path(), "1.1.0"));
+        let mut dropped = install(&mut db, &fetch(repo, entry).unwrap());
+        dropped.sort();
+        assert_eq!(dropped, vec!["top", "tray"]);
+        assert!(db.contains("panel") && !db.contains("top"));
+        assert_eq!(installed_packages(&db)["bar"], Version::new(1, 1, 0));
+
+        let mut tampered = entry.clone();
+        tampered.sha256 = "0".repeat(64);
+        let err = fetch(repo, &tampered).unwrap_err().to_string();
+        assert!(err.contains("checksum"), "{}", err);
+
+        let mut relabeled = entry.clone();
+        relabeled.version = "2.0.0".to_string();
+        let err = fetch(repo, &relabeled).unwrap_err().to_string();
+        assert!(err.contains("lists it as bar v2.0.0"), "{}", err);
+
+        let mut missing = entry.clone();
+        missing.file = "gone.pkg".to_string();
+        assert!(fetch(repo, &missing).is_err());
+    }
+}
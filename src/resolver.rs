@@ -0,0 +1,176 @@
+use crate::template::Template;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ResolveError {
+    #[error("Dependency cycle detected: {}", .0.join(" -> "))]
+    Cycle(Vec<String>),
+}
+
+/// Order templates so that every template comes after its `depends_on`,
+/// `after` and `overrides` entries. Names that are not part of `templates` are ignored here;
+/// use [`missing_dependencies`] to report them. Ties are broken by name so the
+/// order is stable across runs.
+pub fn apply_order<'a>(templates: &[&'a Template]) -> Result<Vec<&'a Template>, ResolveError> {
+    let by_name: BTreeMap<&str, &'a Template> = templates
+        .iter()
+        .map(|t| (t.manifest.name.as_str(), *t))
+        .collect();
+
+    let mut in_degree: HashMap<&str, usize> = by_name.keys().map(|n| (*n, 0)).collect();
+    let mut dependents: HashMap<&str, Vec<&str>> = HashMap::new();
+
+    for (name, tpl) in &by_name {
+        let edges: BTreeSet<&str> = predecessors(tpl)
+            .filter(|dep| by_name.contains_key(dep) && dep != name)
+            .collect();
+        for dep in edges {
+            dependents.entry(dep).or_default().push(name);
+            *in_degree.get_mut(name).unwrap() += 1;
+        }
+    }
+
+    let mut ready: BTreeSet<&str> = in_degree
+        .iter()
+        .filter(|(_, d)| **d == 0)
+        .map(|(n, _)| *n)
+        .collect();
+    let mut ordered = Vec::with_capacity(by_name.len());
+
+    while let Some(name) = ready.pop_first() {
+        ordered.push(by_name[name]);
+        for dependent in dependents.get(name).into_iter().flatten() {
+            let degree = in_degree.get_mut(dependent).unwrap();
+            *degree -= 1;
+            if *degree == 0 {
+                ready.insert(dependent);
+            }
+        }
+    }
+
+    if ordered.len() < by_name.len() {
+        let remaining: BTreeSet<&str> = in_degree
+            .iter()
+            .filter(|(_, d)| **d > 0)
+            .map(|(n, _)| *n)
+            .collect();
+        return Err(ResolveError::Cycle(find_cycle(&by_name, &remaining)));
+    }
+
+    Ok(ordered)
+}
+
+/// Hard dependencies of `tpl` for which `is_available` returns false.
+pub fn missing_dependencies(tpl: &Template, is_available: impl Fn(&str) -> bool) -> Vec<String> {
+    tpl.manifest
+        .depends_on
+        .iter()
+        .filter(|dep| !is_available(dep))
+        .cloned()
+        .collect()
+}
+
+fn predecessors(tpl: &Template) -> impl Iterator<Item = &str> {
+    tpl.manifest
+        .depends_on
+        .iter()
+        .chain(&tpl.manifest.after)
+        .chain(&tpl.manifest.overrides)
+        .map(String::as_str)
+}
+
+/// Walk predecessor edges inside the unresolved set until a name repeats.
+fn find_cycle(by_name: &BTreeMap<&str, &Template>, remaining: &BTreeSet<&str>) -> Vec<String> {
+    let Some(start) = remaining.first() else {
+        return Vec::new();
+    };
+
+    let mut path: Vec<&str> = vec![start];
+    let mut current = *start;
+    loop {
+        let next = predecessors(by_name[current])
+            .filter(|dep| remaining.contains(dep))
+            .min();
+        let Some(next) = next else {
+            break;
+        };
+        if let Some(pos) = path.iter().position(|n| *n == next) {
+            let mut cycle: Vec<String> = path[pos..].iter().map(|n| n.to_string()).collect();
+            cycle.push(next.to_string());
+            return cycle;
+        }
+        path.push(next);
+        current = next;
+    }
+
+    path.iter().map(|n| n.to_string()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::template::TemplateManifest;
+
+    fn tpl(name: &str, depends_on: &[&str], after: &[&str]) -> Template {
+        Template {
+            manifest: TemplateManifest {
+                name: name.to_string(),
+                version: "0.1.0".to_string(),
+                authors: vec![],
+                description: String::new(),
+                repository: None,
+                license: None,
+                ignored: false,
+                depends_on: depends_on.iter().map(|s| s.to_string()).collect(),
+                after: after.iter().map(|s| s.to_string()).collect(),
+                overrides: vec![],
+            },
+            targets: vec![],
+            files: vec![],
+            hooks: Default::default(),
+        }
+    }
+
+    fn names(list: &[&Template]) -> Vec<String> {
+        list.iter().map(|t| t.manifest.name.clone()).collect()
+    }
+
+    #[test]
+    fn test_apply_order_respects_dependencies() {
+        let starship = tpl("starship", &["starship-sessions"], &[]);
+        let sessions = tpl("starship-sessions", &[], &[]);
+        let waybar = tpl("waybar", &[], &["gtk"]);
+        let gtk = tpl("gtk", &[], &[]);
+
+        let ordered = apply_order(&[&starship, &waybar, &sessions, &gtk]).unwrap();
+        assert_eq!(
+            names(&ordered),
+            vec!["gtk", "starship-sessions", "starship", "waybar"]
+        );
+    }
+
+    #[test]
+    fn test_apply_order_ignores_absent_templates() {
+        let a = tpl("a", &["missing"], &["also-missing"]);
+        let ordered = apply_order(&[&a]).unwrap();
+        assert_eq!(names(&ordered), vec!["a"]);
+        assert_eq!(missing_dependencies(&a, |_| false), vec!["missing"]);
+    }
+
+    #[test]
+    fn test_apply_order_detects_cycle() {
+        let a = tpl("a", &["b"], &[]);
+        let b = tpl("b", &[], &["c"]);
+        let c = tpl("c", &["a"], &[]);
+        let free = tpl("free", &[], &[]);
+
+        match apply_order(&[&a, &b, &c, &free]) {
+            Err(ResolveError::Cycle(path)) => {
+                assert_eq!(path.first(), path.last());
+                assert_eq!(path.len(), 4);
+            }
+            Ok(_) => panic!("expected a cycle"),
+        }
+    }
+}
@@ -0,0 +1,293 @@
+//! Detached Ed25519 signatures for `.pkg` archives.
+//!
+//! A signature sits next to its package as `<file>.pkg.sig` and covers the
+//! exact bytes of the archive. Keys are small TOML files holding hex: the
+//! secret half (`<name>.key`) stays with its owner, the public half
+//! (`<name>.pub`) is what others put into their trusted-keys directory.
+
+use crate::digest;
+use anyhow::{Context, Result, anyhow};
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{self, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+
+pub const SECRET_EXT: &str = "key";
+pub const PUBLIC_EXT: &str = "pub";
+pub const SIGNATURE_EXT: &str = "sig";
+
+#[derive(Serialize, Deserialize)]
+struct SecretKeyFile {
+    name: String,
+    secret_key: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct PublicKeyFile {
+    name: String,
+    public_key: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SignatureFile {
+    /// Name of the key that made the signature, for messages only
+    key: String,
+    public_key: String,
+    signature: String,
+}
+
+/// A secret key and the name it was generated under.
+pub struct KeyPair {
+    pub name: String,
+    signing: SigningKey,
+}
+
+/// A public key, identified to users by its name and fingerprint.
+#[derive(Debug, Clone)]
+pub struct PublicKey {
+    pub name: String,
+    key: VerifyingKey,
+}
+
+impl PublicKey {
+    /// First 16 hex digits of the SHA-256 of the key.
+    pub fn fingerprint(&self) -> String {
+        fingerprint(&self.key)
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read public key {:?}", path))?;
+        let file: PublicKeyFile = toml::from_str(&text)
+            .with_context(|| format!("Failed to parse public key {:?}", path))?;
+        Ok(Self {
+            name: file.name,
+            key: verifying_key(&file.public_key)
+                .with_context(|| format!("Invalid public key in {:?}", path))?,
+        })
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let file = PublicKeyFile {
+            name: self.name.clone(),
+            public_key: digest::to_hex(self.key.as_bytes()),
+        };
+        fs::write(path, toml::to_string_pretty(&file)?)
+            .with_context(|| format!("Failed to write public key {:?}", path))
+    }
+}
+
+impl KeyPair {
+    /// A new key from the OS random number generator.
+    pub fn generate(name: &str) -> io::Result<Self> {
+        let mut seed = [0u8; 32];
+        let mut filled = 0;
+        while filled < seed.len() {
+            let ret = unsafe {
+                libc::getrandom(
+                    seed[filled..].as_mut_ptr() as *mut libc::c_void,
+                    seed.len() - filled,
+                    0,
+                )
+            };
+            if ret < 0 {
+                let err = io::Error::last_os_error();
+                if err.kind() == io::ErrorKind::Interrupted {
+                    continue;
+                }
+                return Err(err);
+            }
+            filled += ret as usize;
+        }
+        Ok(Self {
+            name: name.to_string(),
+            signing: SigningKey::from_bytes(&seed),
+        })
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read secret key {:?}", path))?;
+        let file: SecretKeyFile = toml::from_str(&text)
+            .with_context(|| format!("Failed to parse secret key {:?}", path))?;
+        let bytes: [u8; 32] = digest::from_hex(&file.secret_key)
+            .and_then(|b| b.try_into().ok())
+            .ok_or_else(|| anyhow!("Invalid secret key in {:?}", path))?;
+        Ok(Self {
+            name: file.name,
+            signing: SigningKey::from_bytes(&bytes),
+        })
+    }
+
+    /// Write `<name>.key` (readable by the owner only) and `<name>.pub` into `dir`.
+    pub fn save(&self, dir: &Path) -> Result<(PathBuf, PathBuf)> {
+        fs::create_dir_all(dir)?;
+        let secret_path = dir.join(format!("{}.{}", self.name, SECRET_EXT));
+        let public_path = dir.join(format!("{}.{}", self.name, PUBLIC_EXT));
+
+        let file = toml::to_string_pretty(&SecretKeyFile {
+            name: self.name.clone(),
+            secret_key: digest::to_hex(self.signing.as_bytes()),
+        })?;
+        fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(&secret_path)
+            .and_then(|mut f| f.write_all(file.as_bytes()))
+            .with_context(|| format!("Failed to write secret key {:?}", secret_path))?;
+        self.public().save(&public_path)?;
+        Ok((secret_path, public_path))
+    }
+
+    pub fn public(&self) -> PublicKey {
+        PublicKey {
+            name: self.name.clone(),
+            key: self.signing.verifying_key(),
+        }
+    }
+
+    /// Sign `package` and write the signature next to it, returning its path.
+    pub fn sign_file(&self, package: &Path) -> Result<PathBuf> {
+        let bytes =
+            fs::read(package).with_context(|| format!("Failed to read package {:?}", package))?;
+        let signature = self.signing.sign(&bytes);
+        let file = SignatureFile {
+            key: self.name.clone(),
+            public_key: digest::to_hex(self.signing.verifying_key().as_bytes()),
+            signature: digest::to_hex(&signature.to_bytes()),
+        };
+        let path = signature_path(package);
+        fs::write(&path, toml::to_string_pretty(&file)?)
+            .with_context(|| format!("Failed to write signature {:?}", path))?;
+        Ok(path)
+    }
+}
+
+/// What the signature next to a package says about it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum Verification {
+    /// Signed by a trusted key, and the package is unchanged.
+    Trusted { key: String, fingerprint: String },
+    /// No signature file.
+    Unsigned,
+    /// Intact, but signed by a key that is not trusted.
+    UntrustedKey { key: String, fingerprint: String },
+    /// The signature does not match the package, or cannot be read.
+    Invalid { reason: String },
+}
+
+/// Where the detached signature of `package` lives.
+pub fn signature_path(package: &Path) -> PathBuf {
+    let mut name = package.as_os_str().to_owned();
+    name.push(format!(".{}", SIGNATURE_EXT));
+    PathBuf::from(name)
+}
+
+/// Check the signature of `package` against the `trusted` keys.
+pub fn verify_file(package: &Path, trusted: &[PublicKey]) -> Result<Verification> {
+    let sig_path = signature_path(package);
+    if !sig_path.exists() {
+        return Ok(Verification::Unsigned);
+    }
+    let invalid = |reason: String| Ok(Verification::Invalid { reason });
+
+    let text = fs::read_to_string(&sig_path)?;
+    let file: SignatureFile = match toml::from_str(&text) {
+        Ok(file) => file,
+        Err(e) => return invalid(format!("unreadable signature: {}", e.message())),
+    };
+    let Ok(key) = verifying_key(&file.public_key) else {
+        return invalid("signature names an invalid public key".to_string());
+    };
+    let Some(signature) = digest::from_hex(&file.signature)
+        .and_then(|b| <[u8; 64]>::try_from(b).ok())
+        .map(|b| Signature::from_bytes(&b))
+    else {
+        return invalid("malformed signature".to_string());
+    };
+
+    let bytes = fs::read(package)?;
+    if key.verify(&bytes, &signature).is_err() {
+        return invalid(format!(
+            "package does not match the signature by {} ({})",
+            file.key,
+            fingerprint(&key)
+        ));
+    }
+
+    Ok(match trusted.iter().find(|t| t.key == key) {
+        Some(t) => Verification::Trusted {
+            key: t.name.clone(),
+            fingerprint: t.fingerprint(),
+        },
+        None => Verification::UntrustedKey {
+            key: file.key,
+            fingerprint: fingerprint(&key),
+        },
+    })
+}
+
+/// Every `*.pub` in `dir`; a missing directory trusts nobody.
+pub fn load_trusted(dir: &Path) -> Result<Vec<PublicKey>> {
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+    let mut paths: Vec<_> = fs::read_dir(dir)?
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| p.extension().is_some_and(|ext| ext == PUBLIC_EXT))
+        .collect();
+    paths.sort();
+    paths.iter().map(|p| PublicKey::load(p)).collect()
+}
+
+fn verifying_key(hex: &str) -> Result<VerifyingKey> {
+    let bytes: [u8; 32] = digest::from_hex(hex)
+        .and_then(|b| b.try_into().ok())
+        .ok_or_else(|| anyhow!("public key is not 32 bytes of hex"))?;
+    Ok(VerifyingKey::from_bytes(&bytes)?)
+}
+
+fn fingerprint(key: &VerifyingKey) -> String {
+    digest::sha256_hex(key.as_bytes())[..16].to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_sign_and_verify() {
+        let dir = tempdir().unwrap();
+        let pkg = dir.path().join("demo.pkg");
+        fs::write(&pkg, b"package bytes").unwrap();
+
+        let keys = KeyPair::generate("alice").unwrap();
+        let (secret, public) = keys.save(&dir.path().join("keys")).unwrap();
+        let keys = KeyPair::load(&secret).unwrap();
+        let trusted = vec![PublicKey::load(&public).unwrap()];
+
+        assert_eq!(verify_file(&pkg, &trusted).unwrap(), Verification::Unsigned);
+
+        keys.sign_file(&pkg).unwrap();
+        assert!(matches!(
+            verify_file(&pkg, &trusted).unwrap(),
+            Verification::Trusted { key, .. } if key == "alice"
+        ));
+        assert!(matches!(
+            verify_file(&pkg, &[]).unwrap(),
+            Verification::UntrustedKey { .. }
+        ));
+
+        fs::write(&pkg, b"package bytes, changed").unwrap();
+        assert!(matches!(
+            verify_file(&pkg, &trusted).unwrap(),
+            Verification::Invalid { .. }
+        ));
+    }
+}
@@ -0,0 +1,202 @@
+//! Drift between what `apply` last wrote and what is on disk now.
+
+use crate::config::Config;
+use crate::db::Store;
+use crate::digest;
+use crate::lint;
+use crate::paths::{self, PathPolicy};
+use crate::processor;
+use crate::template::Template;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TargetState {
+    /// The file matches the last write and the current render.
+    InSync,
+    /// The file was edited since hyprink last wrote it.
+    Modified,
+    /// The file was written once but is gone now.
+    Missing,
+    /// The file is untouched, but the config or template changed since.
+    Stale,
+    /// hyprink has no record of writing this target.
+    Unapplied,
+}
+
+impl TargetState {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TargetState::InSync => "in-sync",
+            TargetState::Modified => "modified",
+            TargetState::Missing => "missing",
+            TargetState::Stale => "stale",
+            TargetState::Unapplied => "unapplied",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TargetStatus {
+    pub target: String,
+    pub path: PathBuf,
+    pub state: TargetState,
+    /// Why the current render could not be produced, if it failed. The state
+    /// then only reflects the file against the last write.
+    pub error: Option<String>,
+    /// What `apply` would write now.
+    #[serde(skip)]
+    pub rendered: Option<String>,
+}
+
+/// State of every output of `tpl`, rendered with `config`.
+pub fn template_status(
+    tpl: &Template,
+    db: &Store,
+    config: &Config,
+    policy: &PathPolicy,
+) -> Vec<TargetStatus> {
+    let (mut tera, ctx) = processor::render_context(config);
+
+    tpl.outputs()
+        .map(|out| {
+            let path = match policy.resolve(&out.target) {
+                Ok(path) => path,
+                Err(e) => {
+                    return TargetStatus {
+                        target: out.target.clone(),
+                        path: paths::expand_target(&out.target),
+                        state: TargetState::Unapplied,
+                        error: Some(e.to_string()),
+                        rendered: None,
+                    };
+                }
+            };
+            let (rendered, error) = match tera.render_str(&out.content, &ctx) {
+                Ok(r) => (Some(r), None),
+                Err(e) => (None, Some(lint::describe(&e))),
+            };
+            let state = state_of(&path, db, rendered.as_deref());
+            TargetStatus {
+                target: out.target.clone(),
+                path,
+                state,
+                error,
+                rendered,
+            }
+        })
+        .collect()
+}
+
+fn state_of(path: &Path, db: &Store, rendered: Option<&str>) -> TargetState {
+    let Some(record) = db.write_record(path) else {
+        return TargetState::Unapplied;
+    };
+    let Ok(current) = digest::sha256_file(path) else {
+        return TargetState::Missing;
+    };
+    if current != record.checksum {
+        return TargetState::Modified;
+    }
+    match rendered {
+        Some(r) if digest::sha256_hex(r.as_bytes()) != record.checksum => TargetState::Stale,
+        _ => TargetState::InSync,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{PathsSection, test_config};
+    use crate::db::WriteRecord;
+    use crate::processor::ApplyOptions;
+    use crate::template::{Target, TemplateManifest};
+    use std::fs;
+    use tempfile::tempdir;
+
+    fn state(tpl: &Template, db: &Store, config: &Config, policy: &PathPolicy) -> TargetState {
+        template_status(tpl, db, config, policy)[0].state
+    }
+
+    #[test]
+    fn test_drift_states_and_apply_guard() {
+        let dir = tempdir().unwrap();
+        let out = dir.path().join("out.conf");
+        let tpl = Template {
+            manifest: TemplateManifest {
+                name: "demo".to_string(),
+                version: "0.1.0".to_string(),
+                authors: vec![],
+                description: String::new(),
+                repository: None,
+                license: None,
+                ignored: false,
+                depends_on: vec![],
+                after: vec![],
+                overrides: vec![],
+            },
+            targets: vec![Target {
+                target: out.display().to_string(),
+                content: "bg = {{ colors.bg }}".to_string(),
+            }],
+            files: vec![],
+            hooks: Default::default(),
+        };
+        let mut config = test_config();
+        let policy = PathPolicy::from_config(&PathsSection {
+            allowed_roots: vec![],
+            allow_outside: true,
+        })
+        .without_staging();
+        let mut db = Store::default();
+        let apply = |db: &Store, config: &Config, force: bool| {
+            let opts = ApplyOptions {
+                force,
+                policy: policy.clone(),
+                last_written: db.written_checksums(),
+                ..Default::default()
+            };
+            processor::apply(&tpl, config, &opts).unwrap()
+        };
+        let record = |db: &mut Store, outcome: processor::TemplateOutcome| {
+            for file in outcome.written {
+                db.record_write(
+                    &file.path,
+                    WriteRecord {
+                        template: "demo".to_string(),
+                        target: file.target,
+                        checksum: file.checksum,
+                        written_at: 0,
+                    },
+                );
+            }
+        };
+
+        assert_eq!(state(&tpl, &db, &config, &policy), TargetState::Unapplied);
+
+        let outcome = apply(&db, &config, false);
+        record(&mut db, outcome);
+        assert_eq!(state(&tpl, &db, &config, &policy), TargetState::InSync);
+
+        fs::write(&out, "bg = #ffffff").unwrap();
+        assert_eq!(state(&tpl, &db, &config, &policy), TargetState::Modified);
+        let outcome = apply(&db, &config, false);
+        assert_eq!(outcome.modified, vec![out.clone()]);
+        assert!(outcome.written.is_empty());
+        assert_eq!(fs::read_to_string(&out).unwrap(), "bg = #ffffff");
+
+        let outcome = apply(&db, &config, true);
+        record(&mut db, outcome);
+        assert_eq!(state(&tpl, &db, &config, &policy), TargetState::InSync);
+
+        config
+            .theme
+            .colors
+            .insert("bg".to_string(), "#000000".to_string());
+        assert_eq!(state(&tpl, &db, &config, &policy), TargetState::Stale);
+
+        fs::remove_file(&out).unwrap();
+        assert_eq!(state(&tpl, &db, &config, &policy), TargetState::Missing);
+    }
+}
@@ -1,3 +1,4 @@
+use semver::Version;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -12,6 +13,18 @@ pub struct Template {
     pub hooks: Hooks,
 }
 
+impl Template {
+    /// `targets` followed by `files`, in the order they are written.
+    pub fn outputs(&self) -> impl Iterator<Item = &Target> {
+        self.targets.iter().chain(&self.files)
+    }
+
+    /// Every path this template writes, from both `targets` and `files`.
+    pub fn target_paths(&self) -> impl Iterator<Item = &str> {
+        self.outputs().map(|t| t.target.as_str())
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct TemplateManifest {
     pub name: String,
@@ -22,6 +35,15 @@ pub struct TemplateManifest {
     pub license: Option<String>,
     #[serde(default)]
     pub ignored: bool,
+    /// Templates that must be installed; they are always applied first.
+    #[serde(default)]
+    pub depends_on: Vec<String>,
+    /// Templates that are applied first when present, without requiring them.
+    #[serde(default)]
+    pub after: Vec<String>,
+    /// Templates whose targets this one may overwrite; it is applied after them.
+    #[serde(default)]
+    pub overrides: Vec<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -34,3 +56,16 @@ pub struct Target {
 pub struct Hooks {
     pub reload: Option<String>,
 }
+
+/// Parse a manifest version as semver, accepting a leading `v` and
+/// short forms like `1` or `0.2`.
+pub fn parse_version(version: &str) -> Option<Version> {
+    let version = version.trim().trim_start_matches('v');
+    Version::parse(version)
+        .ok()
+        .or_else(|| match version.split('.').count() {
+            1 => Version::parse(&format!("{}.0.0", version)).ok(),
+            2 => Version::parse(&format!("{}.0", version)).ok(),
+            _ => None,
+        })
+}
@@ -0,0 +1,169 @@
+//! Themes installed from packages.
+//!
+//! Each theme is a `<name>.toml` in the themes directory, in the same format
+//! as `render --theme` files. At most one is selected at a time; it is laid
+//! over `hyprink.conf` whenever the config is loaded, so switching themes
+//! never edits the user's own config.
+
+use crate::config::{Config, ThemeFile};
+use anyhow::{Context, Result, bail};
+use log::debug;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// File in the themes directory holding the name of the selected theme.
+const SELECTED: &str = "selected";
+
+/// A theme in the themes directory.
+#[derive(Debug, Clone)]
+pub struct InstalledTheme {
+    /// Name to select it by (the file stem)
+    pub name: String,
+    pub path: PathBuf,
+    /// `name` from the theme's own `[theme]` table
+    pub title: Option<String>,
+}
+
+/// Write `content` as theme `name`, replacing a theme of that name. Returns
+/// whether one was replaced.
+pub fn install(dir: &Path, name: &str, content: &str) -> Result<bool> {
+    check_name(name)?;
+    ThemeFile::parse(content).with_context(|| format!("Failed to parse theme {}", name))?;
+    fs::create_dir_all(dir)?;
+    let path = theme_path(dir, name);
+    let replaced = path.exists();
+    fs::write(&path, content).with_context(|| format!("Failed to write theme {:?}", path))?;
+    debug!("Installed theme {} to {:?}", name, path);
+    Ok(replaced)
+}
+
+/// Every installed theme, sorted by name.
+pub fn list(dir: &Path) -> Result<Vec<InstalledTheme>> {
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+    let mut themes: Vec<InstalledTheme> = fs::read_dir(dir)?
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| p.extension().is_some_and(|ext| ext == "toml"))
+        .filter_map(|path| {
+            let name = path.file_stem()?.to_string_lossy().to_string();
+            let title = ThemeFile::load(&path)
+                .ok()
+                .and_then(|f| f.name().map(str::to_string));
+            Some(InstalledTheme { name, path, title })
+        })
+        .collect();
+    themes.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(themes)
+}
+
+/// Delete theme `name`, deselecting it if it was selected. Returns false if
+/// there was no such theme.
+pub fn remove(dir: &Path, name: &str) -> Result<bool> {
+    check_name(name)?;
+    let path = theme_path(dir, name);
+    if !path.exists() {
+        return Ok(false);
+    }
+    fs::remove_file(&path)?;
+    if selected(dir).as_deref() == Some(name) {
+        select(dir, None)?;
+    }
+    Ok(true)
+}
+
+/// Files that change when another theme is selected or the selected theme
+/// is edited: the selection and the selected theme itself.
+pub fn selection_files(dir: &Path) -> Vec<PathBuf> {
+    let mut files = vec![dir.join(SELECTED)];
+    files.extend(selected(dir).map(|name| theme_path(dir, &name)));
+    files
+}
+
+/// The selected theme, if any.
+pub fn selected(dir: &Path) -> Option<String> {
+    let name = fs::read_to_string(dir.join(SELECTED)).ok()?;
+    let name = name.trim();
+    (!name.is_empty()).then(|| name.to_string())
+}
+
+/// Select theme `name`, or go back to `hyprink.conf` alone with `None`.
+pub fn select(dir: &Path, name: Option<&str>) -> Result<()> {
+    let path = dir.join(SELECTED);
+    match name {
+        Some(name) => {
+            check_name(name)?;
+            if !theme_path(dir, name).is_file() {
+                bail!("theme '{}' is not installed", name);
+            }
+            fs::write(&path, format!("{}\n", name))?;
+        }
+        None if path.exists() => fs::remove_file(&path)?,
+        None => {}
+    }
+    Ok(())
+}
+
+/// Lay the selected theme over `config`. Returns its name, or `None` if no
+/// theme is selected.
+pub fn overlay_selected(dir: &Path, config: &mut Config) -> Result<Option<String>> {
+    let Some(name) = selected(dir) else {
+        return Ok(None);
+    };
+    let file = ThemeFile::load(&theme_path(dir, &name))
+        .with_context(|| format!("Failed to load selected theme '{}'", name))?;
+    config.overlay(file);
+    debug!("Applied selected theme {}", name);
+    Ok(Some(name))
+}
+
+fn theme_path(dir: &Path, name: &str) -> PathBuf {
+    dir.join(format!("{}.toml", name))
+}
+
+/// Fail unless `name` can be a theme: a plain file stem, not a path.
+pub fn check_name(name: &str) -> Result<()> {
+    let valid = !name.is_empty()
+        && name != SELECTED
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if !valid {
+        bail!("invalid theme name '{}'", name);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_selected_theme_overlays_config() {
+        let dir = tempdir().unwrap();
+        let theme = "[theme]\nname = \"Sweet Dracula\"\ncolors = { bg = \"#282a36\" }\n\n[icons.ascii]\nok = \"[ok]\"\n";
+        assert!(!install(dir.path(), "sweet-dracula", theme).unwrap());
+        assert!(install(dir.path(), "../escape", theme).is_err());
+        assert!(select(dir.path(), Some("missing")).is_err());
+
+        let mut config = config::test_config();
+        assert_eq!(overlay_selected(dir.path(), &mut config).unwrap(), None);
+
+        select(dir.path(), Some("sweet-dracula")).unwrap();
+        let installed = list(dir.path()).unwrap();
+        assert_eq!(installed.len(), 1);
+        assert_eq!(installed[0].title.as_deref(), Some("Sweet Dracula"));
+
+        overlay_selected(dir.path(), &mut config).unwrap();
+        assert_eq!(config.theme.name, "Sweet Dracula");
+        assert_eq!(config.theme.colors["bg"], "#282a36");
+        assert_eq!(config.theme.colors["fg"], "white");
+        assert_eq!(config.icons.sets["ascii"]["ok"], "[ok]");
+
+        assert!(remove(dir.path(), "sweet-dracula").unwrap());
+        assert_eq!(selected(dir.path()), None);
+    }
+}
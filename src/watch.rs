@@ -0,0 +1,170 @@
+//! Minimal inotify wrapper for `hyprink watch`.
+//!
+//! Directories are watched rather than files: editors commonly save by
+//! writing a temp file and renaming it over the original, which replaces the
+//! inode a file watch would be attached to.
+
+use log::debug;
+use std::collections::HashMap;
+use std::ffi::{CString, OsStr};
+use std::io;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+const EVENT_MASK: u32 = libc::IN_CLOSE_WRITE
+    | libc::IN_MOVED_TO
+    | libc::IN_MOVED_FROM
+    | libc::IN_CREATE
+    | libc::IN_DELETE;
+
+pub struct Watcher {
+    fd: OwnedFd,
+    dirs: HashMap<i32, PathBuf>,
+}
+
+impl Watcher {
+    pub fn new() -> io::Result<Self> {
+        let fd = unsafe { libc::inotify_init1(libc::IN_CLOEXEC | libc::IN_NONBLOCK) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Self {
+            fd: unsafe { OwnedFd::from_raw_fd(fd) },
+            dirs: HashMap::new(),
+        })
+    }
+
+    /// Watch the entries of `dir`. Adding a directory twice is a no-op.
+    pub fn add_dir(&mut self, dir: &Path) -> io::Result<()> {
+        let c_path = CString::new(dir.as_os_str().as_bytes())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        let wd =
+            unsafe { libc::inotify_add_watch(self.fd.as_raw_fd(), c_path.as_ptr(), EVENT_MASK) };
+        if wd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        if self.dirs.insert(wd, dir.to_path_buf()).is_none() {
+            debug!("Watching {:?}", dir);
+        }
+        Ok(())
+    }
+
+    /// Block until something changes, then keep collecting until nothing has
+    /// changed for `quiet`. Returns every path touched during the burst.
+    pub fn wait_debounced(&mut self, quiet: Duration) -> io::Result<Vec<PathBuf>> {
+        let mut changed = self.wait(None)?;
+        let started = Instant::now();
+        loop {
+            let more = self.wait(Some(quiet))?;
+            if more.is_empty() {
+                break;
+            }
+            changed.extend(more);
+            debug!("Still changing after {:?}", started.elapsed());
+        }
+        changed.sort();
+        changed.dedup();
+        Ok(changed)
+    }
+
+    /// Paths changed in the next batch of events, or nothing after `timeout`.
+    fn wait(&mut self, timeout: Option<Duration>) -> io::Result<Vec<PathBuf>> {
+        let mut pfd = libc::pollfd {
+            fd: self.fd.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        let timeout_ms = timeout.map_or(-1, |t| t.as_millis().min(i32::MAX as u128) as i32);
+        loop {
+            let ret = unsafe { libc::poll(&mut pfd, 1, timeout_ms) };
+            if ret < 0 {
+                let err = io::Error::last_os_error();
+                if err.kind() == io::ErrorKind::Interrupted {
+                    continue;
+                }
+                return Err(err);
+            }
+            if ret == 0 {
+                return Ok(Vec::new());
+            }
+            break;
+        }
+        self.read_events()
+    }
+
+    fn read_events(&mut self) -> io::Result<Vec<PathBuf>> {
+        let mut buf = [0u8; 4096];
+        let mut changed = Vec::new();
+        loop {
+            let n = unsafe {
+                libc::read(
+                    self.fd.as_raw_fd(),
+                    buf.as_mut_ptr() as *mut libc::c_void,
+                    buf.len(),
+                )
+            };
+            if n < 0 {
+                let err = io::Error::last_os_error();
+                if err.kind() == io::ErrorKind::WouldBlock {
+                    return Ok(changed);
+                }
+                return Err(err);
+            }
+            if n == 0 {
+                return Ok(changed);
+            }
+
+            let header = std::mem::size_of::<libc::inotify_event>();
+            let mut offset = 0;
+            while offset + header <= n as usize {
+                let event = unsafe {
+                    std::ptr::read_unaligned(buf.as_ptr().add(offset) as *const libc::inotify_event)
+                };
+                let name_start = offset + header;
+                let name_end = name_start + event.len as usize;
+                offset = name_end;
+
+                if event.mask & libc::IN_IGNORED != 0 {
+                    self.dirs.remove(&event.wd);
+                    continue;
+                }
+                let Some(dir) = self.dirs.get(&event.wd) else {
+                    continue;
+                };
+                let name = &buf[name_start..name_end.min(n as usize)];
+                let name = name.split(|b| *b == 0).next().unwrap_or_default();
+                if name.is_empty() {
+                    changed.push(dir.clone());
+                } else {
+                    changed.push(dir.join(OsStr::from_bytes(name)));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_watcher_reports_debounced_changes() {
+        let dir = tempdir().unwrap();
+        let mut watcher = Watcher::new().unwrap();
+        watcher.add_dir(dir.path()).unwrap();
+
+        fs::write(dir.path().join("a.tpl"), "one").unwrap();
+        fs::write(dir.path().join("a.tpl"), "two").unwrap();
+        fs::write(dir.path().join("b.tpl"), "three").unwrap();
+
+        let changed = watcher.wait_debounced(Duration::from_millis(50)).unwrap();
+        assert_eq!(
+            changed,
+            vec![dir.path().join("a.tpl"), dir.path().join("b.tpl")]
+        );
+    }
+}